@@ -0,0 +1,320 @@
+//! Code generation of Protofish services from `.proto` files.
+//!
+//! For every service, [`ServiceGenerator`] emits
+//! - a client, e.g. `GreeterClient`, calling the methods on a connection,
+//! - a server trait, e.g. `Greeter`, implemented by the application,
+//! - a wrapper, e.g. `GreeterServer`, serving the trait through
+//!   `protofish::rpc::Router`.
+//!
+//! The generated code needs the `rpc` feature of `protofish`.
+//!
+//! ```no_run
+//! // build.rs
+//! fn main() -> std::io::Result<()> {
+//!     protofish_build::configure().compile_protos(&["proto/greeter.proto"], &["proto"])
+//! }
+//! ```
+
+use std::fmt::{self, Write};
+
+use prost_build::{Method, Service};
+
+/// Returns a `prost_build::Config` generating Protofish services.
+pub fn configure() -> prost_build::Config {
+    let mut config = prost_build::Config::new();
+    config.service_generator(Box::new(ServiceGenerator::new()));
+
+    config
+}
+
+/// Generates clients and server traits of Protofish services.
+#[derive(Debug, Default)]
+pub struct ServiceGenerator {}
+
+impl ServiceGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl prost_build::ServiceGenerator for ServiceGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        generate_client(&service, buf)
+            .and_then(|()| generate_trait(&service, buf))
+            .and_then(|()| generate_server(&service, buf))
+            .expect("writing to a String does not fail");
+    }
+}
+
+/// How a method exchanges messages.
+enum Mode {
+    Unary,
+    ServerStreaming,
+    ClientStreaming,
+    Bidi,
+}
+
+impl Mode {
+    fn of(method: &Method) -> Self {
+        match (method.client_streaming, method.server_streaming) {
+            (false, false) => Mode::Unary,
+            (false, true) => Mode::ServerStreaming,
+            (true, false) => Mode::ClientStreaming,
+            (true, true) => Mode::Bidi,
+        }
+    }
+}
+
+fn full_name(service: &Service) -> String {
+    if service.package.is_empty() {
+        service.proto_name.clone()
+    } else {
+        format!("{}.{}", service.package, service.proto_name)
+    }
+}
+
+fn generate_client(service: &Service, buf: &mut String) -> fmt::Result {
+    let full_name = full_name(service);
+    let client = format!("{}Client", service.name);
+
+    writeln!(buf, "/// Client of the `{}` service.", full_name)?;
+    writeln!(buf, "pub struct {}<U: ::protofish::utp::UTP> {{", client)?;
+    writeln!(buf, "    inner: ::protofish::rpc::Client<U>,")?;
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    writeln!(
+        buf,
+        "impl<U: ::protofish::utp::UTP> ::core::clone::Clone for {}<U> {{",
+        client
+    )?;
+    writeln!(buf, "    fn clone(&self) -> Self {{")?;
+    writeln!(buf, "        Self {{ inner: self.inner.clone() }}")?;
+    writeln!(buf, "    }}")?;
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    writeln!(buf, "impl<U: ::protofish::utp::UTP> {}<U> {{", client)?;
+    writeln!(
+        buf,
+        "    pub fn new(conn: ::std::sync::Arc<::protofish::Connection<U>>) -> Self {{"
+    )?;
+    writeln!(
+        buf,
+        "        Self {{ inner: ::protofish::rpc::Client::new(conn) }}"
+    )?;
+    writeln!(buf, "    }}")?;
+
+    for method in &service.methods {
+        let path = format!("/{}/{}", full_name, method.proto_name);
+        let (input, output) = (&method.input_type, &method.output_type);
+
+        writeln!(buf)?;
+        method.comments.append_with_indent(1, buf);
+        match Mode::of(method) {
+            Mode::Unary => {
+                writeln!(
+                    buf,
+                    "    pub async fn {}(&self, request: &{}) -> ::core::result::Result<{}, ::protofish::ArbError> {{",
+                    method.name, input, output
+                )?;
+                writeln!(buf, "        self.inner.unary({:?}, request).await", path)?;
+            }
+            Mode::ServerStreaming => {
+                writeln!(
+                    buf,
+                    "    pub async fn {}(&self, request: &{}) -> ::core::result::Result<::protofish::rpc::Streaming<{}>, ::protofish::ArbError> {{",
+                    method.name, input, output
+                )?;
+                writeln!(
+                    buf,
+                    "        self.inner.server_streaming({:?}, request).await",
+                    path
+                )?;
+            }
+            Mode::ClientStreaming => {
+                writeln!(
+                    buf,
+                    "    pub async fn {}(&self) -> ::core::result::Result<::protofish::rpc::ClientStreaming<{}, {}>, ::protofish::ArbError> {{",
+                    method.name, input, output
+                )?;
+                writeln!(buf, "        self.inner.client_streaming({:?}).await", path)?;
+            }
+            Mode::Bidi => {
+                writeln!(
+                    buf,
+                    "    pub async fn {}(&self) -> ::core::result::Result<(::protofish::rpc::MessageSink<{}>, ::protofish::rpc::Streaming<{}>), ::protofish::ArbError> {{",
+                    method.name, input, output
+                )?;
+                writeln!(buf, "        self.inner.bidi_streaming({:?}).await", path)?;
+            }
+        }
+        writeln!(buf, "    }}")?;
+    }
+
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    Ok(())
+}
+
+fn generate_trait(service: &Service, buf: &mut String) -> fmt::Result {
+    service.comments.append_with_indent(0, buf);
+    writeln!(buf, "#[::protofish::rpc::async_trait]")?;
+    writeln!(
+        buf,
+        "pub trait {}: ::core::marker::Send + ::core::marker::Sync + 'static {{",
+        service.name
+    )?;
+
+    for (i, method) in service.methods.iter().enumerate() {
+        if i > 0 {
+            writeln!(buf)?;
+        }
+
+        let (input, output) = (&method.input_type, &method.output_type);
+        let (params, returns) = match Mode::of(method) {
+            Mode::Unary => (format!("request: {}", input), output.clone()),
+            Mode::ServerStreaming => (
+                format!(
+                    "request: {}, responses: ::protofish::rpc::MessageSink<{}>",
+                    input, output
+                ),
+                "()".to_string(),
+            ),
+            Mode::ClientStreaming => (
+                format!("requests: ::protofish::rpc::Streaming<{}>", input),
+                output.clone(),
+            ),
+            Mode::Bidi => (
+                format!(
+                    "requests: ::protofish::rpc::Streaming<{}>, responses: ::protofish::rpc::MessageSink<{}>",
+                    input, output
+                ),
+                "()".to_string(),
+            ),
+        };
+
+        method.comments.append_with_indent(1, buf);
+        writeln!(
+            buf,
+            "    async fn {}(&self, {}) -> ::core::result::Result<{}, ::protofish::rpc::Status>;",
+            method.name, params, returns
+        )?;
+    }
+
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    Ok(())
+}
+
+fn generate_server(service: &Service, buf: &mut String) -> fmt::Result {
+    let full_name = full_name(service);
+    let server = format!("{}Server", service.name);
+    let name = &service.name;
+
+    writeln!(
+        buf,
+        "/// Serves [`{}`] through a `protofish::rpc::Router`.",
+        name
+    )?;
+    writeln!(buf, "pub struct {}<T: {}> {{", server, name)?;
+    writeln!(buf, "    inner: ::std::sync::Arc<T>,")?;
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    writeln!(buf, "impl<T: {}> {}<T> {{", name, server)?;
+    writeln!(buf, "    pub fn new(inner: T) -> Self {{")?;
+    writeln!(buf, "        Self::from_arc(::std::sync::Arc::new(inner))")?;
+    writeln!(buf, "    }}")?;
+    writeln!(buf)?;
+    writeln!(
+        buf,
+        "    pub fn from_arc(inner: ::std::sync::Arc<T>) -> Self {{"
+    )?;
+    writeln!(buf, "        Self {{ inner }}")?;
+    writeln!(buf, "    }}")?;
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    writeln!(buf, "#[::protofish::rpc::async_trait]")?;
+    writeln!(
+        buf,
+        "impl<T: {}> ::protofish::rpc::Service for {}<T> {{",
+        name, server
+    )?;
+    writeln!(buf, "    fn name(&self) -> &'static str {{")?;
+    writeln!(buf, "        {:?}", full_name)?;
+    writeln!(buf, "    }}")?;
+    writeln!(buf)?;
+    writeln!(
+        buf,
+        "    async fn call(&self, method: &str, call: ::protofish::rpc::ServerCall) -> ::core::result::Result<(), ::protofish::rpc::Status> {{"
+    )?;
+    writeln!(buf, "        match method {{")?;
+
+    for method in &service.methods {
+        let (input, output) = (&method.input_type, &method.output_type);
+
+        let mode = Mode::of(method);
+        // streamed requests are handed to the trait as they are
+        let binding = match mode {
+            Mode::Unary | Mode::ServerStreaming => "mut requests",
+            Mode::ClientStreaming | Mode::Bidi => "requests",
+        };
+
+        writeln!(buf, "            {:?} => {{", method.proto_name)?;
+        writeln!(
+            buf,
+            "                let ({}, responses) = call.into_parts::<{}, {}>();",
+            binding, input, output
+        )?;
+        match mode {
+            Mode::Unary => {
+                writeln!(
+                    buf,
+                    "                let request = requests.single().await?;"
+                )?;
+                writeln!(
+                    buf,
+                    "                let response = self.inner.{}(request).await?;",
+                    method.name
+                )?;
+                writeln!(buf, "                responses.send(&response).await?;")?;
+                writeln!(buf, "                Ok(())")?;
+            }
+            Mode::ServerStreaming => {
+                writeln!(
+                    buf,
+                    "                let request = requests.single().await?;"
+                )?;
+                writeln!(
+                    buf,
+                    "                self.inner.{}(request, responses).await",
+                    method.name
+                )?;
+            }
+            Mode::ClientStreaming => {
+                writeln!(
+                    buf,
+                    "                let response = self.inner.{}(requests).await?;",
+                    method.name
+                )?;
+                writeln!(buf, "                responses.send(&response).await?;")?;
+                writeln!(buf, "                Ok(())")?;
+            }
+            Mode::Bidi => {
+                writeln!(
+                    buf,
+                    "                self.inner.{}(requests, responses).await",
+                    method.name
+                )?;
+            }
+        }
+        writeln!(buf, "            }}")?;
+    }
+
+    writeln!(
+        buf,
+        "            _ => Err(::protofish::rpc::Status::not_found(::std::format!(\"unknown method {{}}\", method))),"
+    )?;
+    writeln!(buf, "        }}")?;
+    writeln!(buf, "    }}")?;
+    writeln!(buf, "}}")?;
+    Ok(())
+}
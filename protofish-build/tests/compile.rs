@@ -0,0 +1,31 @@
+use std::{fs, path::PathBuf};
+
+/// Runs prost-build with the generator on `proto/echo.proto` and checks that
+/// the generated module compiles against `protofish`.
+#[test]
+fn test_compile_echo() {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("protofish-build-echo");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    protofish_build::configure()
+        .protoc_executable(protoc_bin_vendored::protoc_bin_path().unwrap())
+        .out_dir(&out_dir)
+        .compile_protos(
+            &[concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/proto/echo.proto"
+            )],
+            &[concat!(env!("CARGO_MANIFEST_DIR"), "/tests/proto")],
+        )
+        .unwrap();
+
+    let generated = out_dir.join("echo.rs");
+    let main = out_dir.join("main.rs");
+    fs::write(
+        &main,
+        format!("include!({:?});\n\nfn main() {{}}\n", generated),
+    )
+    .unwrap();
+
+    trybuild::TestCases::new().pass(main);
+}
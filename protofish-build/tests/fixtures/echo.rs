@@ -0,0 +1,102 @@
+/// Client of the `echo.Echo` service.
+pub struct EchoClient<U: ::protofish::utp::UTP> {
+    inner: ::protofish::rpc::Client<U>,
+}
+
+impl<U: ::protofish::utp::UTP> ::core::clone::Clone for EchoClient<U> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<U: ::protofish::utp::UTP> EchoClient<U> {
+    pub fn new(conn: ::std::sync::Arc<::protofish::Connection<U>>) -> Self {
+        Self { inner: ::protofish::rpc::Client::new(conn) }
+    }
+
+    /// Calls `Say`.
+    pub async fn say(&self, request: &EchoRequest) -> ::core::result::Result<EchoReply, ::protofish::ArbError> {
+        self.inner.unary("/echo.Echo/Say", request).await
+    }
+
+    /// Calls `Repeat`.
+    pub async fn repeat(&self, request: &EchoRequest) -> ::core::result::Result<::protofish::rpc::Streaming<EchoReply>, ::protofish::ArbError> {
+        self.inner.server_streaming("/echo.Echo/Repeat", request).await
+    }
+
+    /// Calls `Collect`.
+    pub async fn collect(&self) -> ::core::result::Result<::protofish::rpc::ClientStreaming<EchoRequest, EchoReply>, ::protofish::ArbError> {
+        self.inner.client_streaming("/echo.Echo/Collect").await
+    }
+
+    /// Calls `Chat`.
+    pub async fn chat(&self) -> ::core::result::Result<(::protofish::rpc::MessageSink<EchoRequest>, ::protofish::rpc::Streaming<EchoReply>), ::protofish::ArbError> {
+        self.inner.bidi_streaming("/echo.Echo/Chat").await
+    }
+}
+
+/// Repeats what it is told.
+#[::protofish::rpc::async_trait]
+pub trait Echo: ::core::marker::Send + ::core::marker::Sync + 'static {
+    /// Calls `Say`.
+    async fn say(&self, request: EchoRequest) -> ::core::result::Result<EchoReply, ::protofish::rpc::Status>;
+
+    /// Calls `Repeat`.
+    async fn repeat(&self, request: EchoRequest, responses: ::protofish::rpc::MessageSink<EchoReply>) -> ::core::result::Result<(), ::protofish::rpc::Status>;
+
+    /// Calls `Collect`.
+    async fn collect(&self, requests: ::protofish::rpc::Streaming<EchoRequest>) -> ::core::result::Result<EchoReply, ::protofish::rpc::Status>;
+
+    /// Calls `Chat`.
+    async fn chat(&self, requests: ::protofish::rpc::Streaming<EchoRequest>, responses: ::protofish::rpc::MessageSink<EchoReply>) -> ::core::result::Result<(), ::protofish::rpc::Status>;
+}
+
+/// Serves [`Echo`] through a `protofish::rpc::Router`.
+pub struct EchoServer<T: Echo> {
+    inner: ::std::sync::Arc<T>,
+}
+
+impl<T: Echo> EchoServer<T> {
+    pub fn new(inner: T) -> Self {
+        Self::from_arc(::std::sync::Arc::new(inner))
+    }
+
+    pub fn from_arc(inner: ::std::sync::Arc<T>) -> Self {
+        Self { inner }
+    }
+}
+
+#[::protofish::rpc::async_trait]
+impl<T: Echo> ::protofish::rpc::Service for EchoServer<T> {
+    fn name(&self) -> &'static str {
+        "echo.Echo"
+    }
+
+    async fn call(&self, method: &str, call: ::protofish::rpc::ServerCall) -> ::core::result::Result<(), ::protofish::rpc::Status> {
+        match method {
+            "Say" => {
+                let (mut requests, responses) = call.into_parts::<EchoRequest, EchoReply>();
+                let request = requests.single().await?;
+                let response = self.inner.say(request).await?;
+                responses.send(&response).await?;
+                Ok(())
+            }
+            "Repeat" => {
+                let (mut requests, responses) = call.into_parts::<EchoRequest, EchoReply>();
+                let request = requests.single().await?;
+                self.inner.repeat(request, responses).await
+            }
+            "Collect" => {
+                let (requests, responses) = call.into_parts::<EchoRequest, EchoReply>();
+                let response = self.inner.collect(requests).await?;
+                responses.send(&response).await?;
+                Ok(())
+            }
+            "Chat" => {
+                let (requests, responses) = call.into_parts::<EchoRequest, EchoReply>();
+                self.inner.chat(requests, responses).await
+            }
+            _ => Err(::protofish::rpc::Status::not_found(::std::format!("unknown method {}", method))),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use prost_build::{Comments, Method, Service, ServiceGenerator as _};
+use protofish_build::ServiceGenerator;
+
+fn comments(lines: &[&str]) -> Comments {
+    Comments {
+        leading_detached: Vec::new(),
+        leading: lines.iter().map(|line| line.to_string()).collect(),
+        trailing: Vec::new(),
+    }
+}
+
+fn method(name: &str, proto_name: &str, client_streaming: bool, server_streaming: bool) -> Method {
+    Method {
+        name: name.into(),
+        proto_name: proto_name.into(),
+        comments: comments(&[&format!(" Calls `{}`.", proto_name)]),
+        input_type: "EchoRequest".into(),
+        output_type: "EchoReply".into(),
+        input_proto_type: ".echo.EchoRequest".into(),
+        output_proto_type: ".echo.EchoReply".into(),
+        options: Default::default(),
+        client_streaming,
+        server_streaming,
+    }
+}
+
+/// The `echo.Echo` service of `fixtures/echo.rs`.
+pub fn echo_service() -> Service {
+    Service {
+        name: "Echo".into(),
+        proto_name: "Echo".into(),
+        package: "echo".into(),
+        comments: comments(&[" Repeats what it is told."]),
+        methods: vec![
+            method("say", "Say", false, false),
+            method("repeat", "Repeat", false, true),
+            method("collect", "Collect", true, false),
+            method("chat", "Chat", true, true),
+        ],
+        options: Default::default(),
+    }
+}
+
+#[test]
+fn test_generate_echo() {
+    let mut buf = String::new();
+    ServiceGenerator::new().generate(echo_service(), &mut buf);
+
+    if std::env::var_os("PROTOFISH_BLESS").is_some() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/echo.rs");
+        std::fs::write(path, &buf).unwrap();
+    }
+
+    assert_eq!(buf, include_str!("fixtures/echo.rs"));
+}
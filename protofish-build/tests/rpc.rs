@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use protofish::{
+    ArbError, Connection, accept, connect,
+    rpc::{MessageSink, Router, Status, Streaming},
+    schema::ErrorType,
+    utp::{UTP, mock_utp_pairs},
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EchoRequest {
+    #[prost(string, tag = "1")]
+    pub text: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EchoReply {
+    #[prost(string, tag = "1")]
+    pub text: String,
+}
+
+include!("fixtures/echo.rs");
+
+struct Parrot;
+
+#[protofish::rpc::async_trait]
+impl Echo for Parrot {
+    async fn say(&self, request: EchoRequest) -> Result<EchoReply, Status> {
+        if request.text.is_empty() {
+            return Err(Status::new(ErrorType::Malformed, "nothing to say"));
+        }
+
+        Ok(EchoReply { text: request.text })
+    }
+
+    async fn repeat(
+        &self,
+        request: EchoRequest,
+        responses: MessageSink<EchoReply>,
+    ) -> Result<(), Status> {
+        for _ in 0..3 {
+            let reply = EchoReply {
+                text: request.text.clone(),
+            };
+            responses.send(&reply).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn collect(&self, mut requests: Streaming<EchoRequest>) -> Result<EchoReply, Status> {
+        let mut text = String::new();
+        while let Some(request) = requests.message().await? {
+            text.push_str(&request.text);
+        }
+
+        Ok(EchoReply { text })
+    }
+
+    async fn chat(
+        &self,
+        mut requests: Streaming<EchoRequest>,
+        responses: MessageSink<EchoReply>,
+    ) -> Result<(), Status> {
+        while let Some(request) = requests.message().await? {
+            let reply = EchoReply {
+                text: request.text.to_uppercase(),
+            };
+            responses.send(&reply).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn connected<U: UTP>(a: U, b: U) -> (Arc<Connection<U>>, Arc<Connection<U>>) {
+    let server = tokio::spawn(async move { accept(b.into()).await });
+    let client = connect(a.into(), "").await.unwrap();
+    let server = server.await.unwrap().unwrap();
+
+    (client.into(), server.into())
+}
+
+fn request(text: &str) -> EchoRequest {
+    EchoRequest { text: text.into() }
+}
+
+#[tokio::test]
+async fn test_rpc_calls() {
+    let (a, b) = mock_utp_pairs();
+    let (client_conn, server_conn) = connected(a, b).await;
+
+    let router = Router::new().add_service(EchoServer::new(Parrot));
+    tokio::spawn(async move { router.serve(&server_conn).await });
+
+    let client = EchoClient::new(client_conn);
+
+    let reply = client.say(&request("hello")).await.unwrap();
+    assert_eq!(reply.text, "hello");
+
+    let failed = client.say(&request("")).await;
+    assert!(matches!(
+        failed,
+        Err(ArbError::Remote {
+            kind: ErrorType::Malformed,
+            ..
+        })
+    ));
+
+    let mut replies = client.repeat(&request("again")).await.unwrap();
+    for _ in 0..3 {
+        assert_eq!(replies.message().await.unwrap().unwrap().text, "again");
+    }
+    assert!(replies.message().await.unwrap().is_none());
+
+    let collect = client.collect().await.unwrap();
+    for text in ["pro", "to", "fish"] {
+        collect.send(&request(text)).await.unwrap();
+    }
+    assert_eq!(collect.finish().await.unwrap().text, "protofish");
+
+    let (requests, mut replies) = client.chat().await.unwrap();
+    for text in ["a", "b"] {
+        requests.send(&request(text)).await.unwrap();
+        let reply = replies.message().await.unwrap().unwrap();
+        assert_eq!(reply.text, text.to_uppercase());
+    }
+    requests.close().await.unwrap();
+    assert!(replies.message().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_rpc_unknown_method() {
+    let (a, b) = mock_utp_pairs();
+    let (client_conn, server_conn) = connected(a, b).await;
+
+    let router = Router::new().add_service(EchoServer::new(Parrot));
+    tokio::spawn(async move { router.serve(&server_conn).await });
+
+    let client = protofish::rpc::Client::new(client_conn);
+    let result = client
+        .unary::<EchoRequest, EchoReply>("/echo.Echo/Shout", &request("hi"))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ArbError::Remote {
+            kind: ErrorType::NotFound,
+            ..
+        })
+    ));
+}
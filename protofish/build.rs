@@ -1,30 +1,30 @@
-use std::{error, fs, path::PathBuf, process::Command};
+use std::{error, fs, path::PathBuf};
 
 use walkdir::WalkDir;
 
 fn main() -> Result<(), Box<dyn error::Error>> {
-    fetch_proto()
+    compile_proto()
 }
 
-fn fetch_proto() -> Result<(), Box<dyn error::Error>> {
-    let out_dir = ".proto";
-    let buf_module = "buf.build/zako-ac/protofish";
+fn compile_proto() -> Result<(), Box<dyn error::Error>> {
+    let proto_dir = "proto";
     let prost_out_dir = "src/prost_generated";
 
-    if !fs::exists(out_dir)? {
-        Command::new("buf")
-            .args(["export", buf_module, "--output", out_dir])
-            .status()?;
+    println!("cargo:rerun-if-changed={}", proto_dir);
+
+    // an explicitly configured protoc takes precedence over the vendored one
+    if std::env::var_os("PROTOC").is_none() {
+        // SAFETY: build scripts are single-threaded
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
     }
 
     let _ = fs::create_dir_all(prost_out_dir);
 
-    let protos = list_protos(out_dir);
-    println!("{:#?}", protos);
+    let protos = list_protos(proto_dir);
 
     prost_build::Config::new()
         .out_dir(prost_out_dir)
-        .compile_protos(&protos, &[out_dir])?;
+        .compile_protos(&protos, &[proto_dir])?;
 
     Ok(())
 }
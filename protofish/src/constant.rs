@@ -1,4 +1,6 @@
-use crate::schema::Version;
+use std::time::Duration;
+
+use crate::schema::{ContextId, Version};
 
 /// Current version of the Protofish protocol implementation.
 pub const VERSION: Version = Version {
@@ -6,3 +8,26 @@ pub const VERSION: Version = Version {
     minor: 0,
     patch: 0,
 };
+
+/// Default deadline for each step of the handshake.
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest number of `AuthChallenge` rounds in a single handshake.
+pub const MAX_AUTH_ROUNDS: usize = 8;
+
+/// Default largest frame, in bytes, sent or received on the PMC stream.
+pub const DEFAULT_MAX_FRAME_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Default deadline for in-flight contexts to finish when closing a connection.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Context reserved for keepalives sent by the client and echoed by the server.
+///
+/// The context ID counter wraps before reaching the reserved IDs.
+pub const CLIENT_KEEPALIVE_CONTEXT_ID: ContextId = u64::MAX - 1;
+
+/// Context reserved for keepalives sent by the server and echoed by the client.
+pub const SERVER_KEEPALIVE_CONTEXT_ID: ContextId = u64::MAX;
+
+/// Context reserved for connection-level messages such as `Close`.
+pub const CONTROL_CONTEXT_ID: ContextId = u64::MAX - 2;
@@ -1,31 +1,143 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use bytes::Bytes;
 
 use crate::{
-    constant::VERSION,
-    core::common::{
-        connection::Connection,
-        context::{ContextReader, ContextWriter},
-        error::ConnectionError,
-        pmc::PMC,
+    constant::{DEFAULT_HANDSHAKE_TIMEOUT, MAX_AUTH_ROUNDS, VERSION},
+    core::{
+        client::credential::CredentialProvider,
+        common::{
+            connection::{Connection, HandshakeOutcome, ResumeToken},
+            context::{ContextReader, ContextWriter},
+            error::ConnectionError,
+            frame::FrameOptions,
+            keepalive::KeepaliveOptions,
+            pmc::PMC,
+            queue::QueueOptions,
+            timeout::read_with_timeout,
+            version::negotiate_version,
+        },
     },
     error::ProtofishError,
-    schema::{ClientHello, IntegrityType, Payload},
+    schema::{ClientHello, Error, ErrorType, Extensions, IntegrityType, Payload},
     utp::{UTP, UTPStream},
 };
 
+/// Options for establishing Protofish connections as a client.
+#[derive(Clone)]
+pub struct ConnectOptions {
+    /// Extensions supported by the client.
+    pub extensions: Extensions,
+
+    /// Token of a previous connection to resume.
+    pub resume_token: Option<ResumeToken>,
+
+    /// Credentials presented to the server's authenticator.
+    pub credentials: Option<Bytes>,
+
+    /// Provider answering authentication challenges from the server.
+    pub credential_provider: Option<Arc<dyn CredentialProvider>>,
+
+    /// Deadline for each server message during the handshake, i.e. the
+    /// `ServerHello` and any `AuthChallenge`.
+    ///
+    /// `None` waits indefinitely.
+    pub hello_timeout: Option<Duration>,
+
+    /// Heartbeat settings; keepalives are not sent when `None`.
+    pub keepalive: Option<KeepaliveOptions>,
+
+    /// Limits of the queues of incoming payloads.
+    pub queue: QueueOptions,
+
+    /// Largest frame size and handling of undecodable frames.
+    pub frame: FrameOptions,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            extensions: Extensions::default(),
+            resume_token: None,
+            credentials: None,
+            credential_provider: None,
+            hello_timeout: Some(DEFAULT_HANDSHAKE_TIMEOUT),
+            keepalive: None,
+            queue: QueueOptions::default(),
+            frame: FrameOptions::default(),
+        }
+    }
+}
+
+impl ConnectOptions {
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn with_resume_token(mut self, resume_token: ResumeToken) -> Self {
+        self.resume_token = Some(resume_token);
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: impl Into<Bytes>) -> Self {
+        self.credentials = Some(credentials.into());
+        self
+    }
+
+    pub fn with_credential_provider(
+        mut self,
+        credential_provider: Arc<dyn CredentialProvider>,
+    ) -> Self {
+        self.credential_provider = Some(credential_provider);
+        self
+    }
+
+    pub fn with_hello_timeout(mut self, hello_timeout: Duration) -> Self {
+        self.hello_timeout = Some(hello_timeout);
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: KeepaliveOptions) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    pub fn with_queue(mut self, queue: QueueOptions) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    pub fn with_frame(mut self, frame: FrameOptions) -> Self {
+        self.frame = frame;
+        self
+    }
+}
+
 /// Establishes a Protofish connection as a client.
 ///
+/// This is [`connect_with`] using the default [`ConnectOptions`].
+pub async fn connect<U>(utp: Arc<U>, hostname: &str) -> Result<Connection<U>, ProtofishError>
+where
+    U: UTP,
+{
+    connect_with(utp, hostname, &ConnectOptions::default()).await
+}
+
+/// Establishes a Protofish connection as a client with the given options.
+///
 /// This function performs the following steps:
 /// 1. Connects to the server via the provided UTP implementation
 /// 2. Opens a reliable stream for the Primary Messaging Channel (PMC)
-/// 3. Performs the client-side handshake by sending `ClientHello`
+/// 3. Performs the client-side handshake by sending `ClientHello`, answering
+///    any authentication challenges with the configured credential provider
 /// 4. Returns a `Connection` if the handshake succeeds
 ///
 /// # Arguments
 ///
 /// * `utp` - An Arc-wrapped UTP implementation for the underlying transport
+/// * `hostname` - The hostname of the server
+/// * `options` - Client-side options such as extensions and a resume token
 ///
 /// # Returns
 ///
@@ -38,44 +150,142 @@ use crate::{
 /// - The UTP connection fails
 /// - Opening the stream fails
 /// - The server rejects the handshake
-pub async fn connect<U>(utp: Arc<U>, hostname: &str) -> Result<Connection<U>, ProtofishError>
+/// - An authentication challenge cannot be answered
+/// - The server does not respond in time, in which case
+///   `ConnectionError::HandshakeTimeout` is returned
+/// - The server speaks an incompatible major protocol version
+pub async fn connect_with<U>(
+    utp: Arc<U>,
+    hostname: &str,
+    options: &ConnectOptions,
+) -> Result<Connection<U>, ProtofishError>
 where
     U: UTP,
 {
     utp.connect(hostname).await?;
 
     let stream = utp.new_stream(IntegrityType::Reliable).await?;
-    let pmc = PMC::new(false, stream);
+    let mut pmc = PMC::with_limits(
+        false,
+        stream,
+        options.queue.clone(),
+        options.frame.max_frame_size,
+        options.frame.decode_policy,
+    );
 
-    let _ = client_handshake(pmc.create_context(), None, hostname.to_string()).await?;
+    let client_hello = ClientHello {
+        version: VERSION,
+        resume_connection_token: options.resume_token.as_ref().map(|t| t.token.to_vec()),
+        hostname: hostname.to_string(),
+        extensions: options.extensions.advertised(options.frame.max_frame_size),
+        credentials: options.credentials.as_ref().map(|c| c.to_vec()),
+    };
+
+    let outcome = client_handshake(
+        pmc.create_context(),
+        client_hello,
+        options.credential_provider.as_deref(),
+        options.hello_timeout,
+    )
+    .await?;
+
+    if let Some(resume_token) = &options.resume_token {
+        pmc.resume(Some(resume_token.counter.clone()));
+    }
 
-    Ok(Connection::new(utp.clone(), pmc))
+    let mut connection = Connection::from_handshake(utp.clone(), pmc, outcome);
+
+    if let Some(keepalive) = &options.keepalive {
+        connection.start_keepalive(keepalive);
+    }
+
+    Ok(connection)
+}
+
+/// Resumes a previously established Protofish connection as a client.
+///
+/// This is [`connect_with`] using a [`ConnectOptions`] with only the resume
+/// token set. It presents the connection token of `token`
+/// in the `ClientHello`. On success the server hands back the same connection
+/// identity, and context IDs on both sides continue where they stopped.
+///
+/// # Arguments
+///
+/// * `utp` - An Arc-wrapped UTP implementation for the new transport connection
+/// * `hostname` - The hostname of the server
+/// * `token` - The token obtained from [`Connection::resume_token`]
+///
+/// # Errors
+///
+/// In addition to the errors of [`connect`], this function returns
+/// `ConnectionError::HandshakeReject` if the server does not know the token
+/// or the session has expired.
+pub async fn resume<U>(
+    utp: Arc<U>,
+    hostname: &str,
+    token: ResumeToken,
+) -> Result<Connection<U>, ProtofishError>
+where
+    U: UTP,
+{
+    let options = ConnectOptions::default().with_resume_token(token);
+    connect_with(utp, hostname, &options).await
 }
 
 async fn client_handshake<S: UTPStream>(
     ctx: (ContextWriter<S>, ContextReader),
-    resume_token: Option<Bytes>,
-    hostname: String,
-) -> Result<Bytes, ProtofishError> {
+    client_hello: ClientHello,
+    credential_provider: Option<&dyn CredentialProvider>,
+    timeout: Option<Duration>,
+) -> Result<HandshakeOutcome, ProtofishError> {
     let (tx, rx) = ctx;
 
-    let client_hello = ClientHello {
-        version: VERSION,
-        resume_connection_token: resume_token.map(Into::into),
-        hostname,
-    };
-
     tx.write(Payload::ClientHello(client_hello)).await?;
 
-    let server_hello = rx.read().await?;
+    let mut server_hello = read_with_timeout(&tx, &rx, timeout, "ServerHello").await?;
+
+    let mut rounds = 0;
+
+    while let Payload::AuthChallenge(challenge) = &server_hello {
+        if rounds == MAX_AUTH_ROUNDS {
+            return deny_auth(&tx, "too many authentication challenges").await;
+        }
+        rounds += 1;
+
+        let response = match credential_provider {
+            Some(provider) => provider.respond(challenge).await,
+            None => None,
+        };
+
+        let Some(response) = response else {
+            return deny_auth(&tx, "unable to answer authentication challenge").await;
+        };
+
+        tx.write(Payload::AuthResponse(response)).await?;
+        server_hello = read_with_timeout(&tx, &rx, timeout, "ServerHello").await?;
+    }
 
     if let Payload::ServerHello(server_hello) = server_hello {
         if server_hello.ok {
-            Ok(server_hello
+            let version = negotiate_version(&VERSION, &server_hello.version).ok_or(
+                ConnectionError::IncompatibleVersion {
+                    local: VERSION,
+                    remote: server_hello.version,
+                },
+            )?;
+
+            let token = server_hello
                 .connection_token
                 .ok_or(ProtofishError::Connection(ConnectionError::MalformedData(
                     "connection token is not provided".into(),
-                )))?)
+                )))?;
+
+            Ok(HandshakeOutcome {
+                token,
+                version,
+                extensions: server_hello.extensions,
+                principal: None,
+            })
         } else {
             let msg = server_hello.message.unwrap_or("unknown error".to_string());
 
@@ -83,31 +293,61 @@ async fn client_handshake<S: UTPStream>(
                 ConnectionError::HandshakeReject(msg),
             ))
         }
+    } else if let Payload::Error(error) = server_hello {
+        Err(ProtofishError::Connection(
+            ConnectionError::HandshakeReject(error.message),
+        ))
     } else {
         Err(ProtofishError::Connection(
-            ConnectionError::MalformedPayload("expected ServerHello".into(), server_hello),
+            ConnectionError::MalformedPayload("expected ServerHello".into(), server_hello.into()),
         ))
     }
 }
 
+/// Tells the server that authentication failed on our side.
+async fn deny_auth<S: UTPStream>(
+    tx: &ContextWriter<S>,
+    message: &str,
+) -> Result<HandshakeOutcome, ProtofishError> {
+    tx.write(Payload::Error(Error {
+        error_type: ErrorType::PermissionDenied,
+        message: message.into(),
+    }))
+    .await?;
+
+    Err(ConnectionError::HandshakeReject(message.into()).into())
+}
+
 #[cfg(test)]
 mod tests {
 
+    use std::time::Duration;
+
     use bytes::BytesMut;
 
     use crate::{
         constant::VERSION,
-        core::{client::client::client_handshake, common::pmc::PMC},
-        schema::{Payload, ServerHello},
-        utp::tests::stream::mock_utp_stream_pairs,
+        core::{
+            client::client::client_handshake,
+            common::{error::ConnectionError, pmc::PMC},
+        },
+        error::ProtofishError,
+        schema::{ClientHello, ErrorType, Payload, ServerHello, Version},
+        utp::tests::stream::{MockUTPStream, mock_utp_stream_pairs},
     };
 
-    #[tokio::test]
-    async fn test_client_handshake_ok() {
-        let (client_stream, server_stream) = mock_utp_stream_pairs(0);
+    fn client_hello(resume_connection_token: Option<Vec<u8>>) -> ClientHello {
+        ClientHello {
+            version: VERSION,
+            resume_connection_token,
+            hostname: "example.com".into(),
+            extensions: Default::default(),
+            credentials: None,
+        }
+    }
 
+    fn spawn_server(server_stream: MockUTPStream, version: Version) {
         let server_pmc = PMC::new(true, server_stream);
-        let client_pmc = PMC::new(false, client_stream);
 
         tokio::spawn(async move {
             let (tx, rx) = server_pmc.next_context().await.unwrap();
@@ -118,7 +358,8 @@ mod tests {
                     ok: true,
                     connection_token: Some(BytesMut::zeroed(20).freeze()),
                     message: None,
-                    version: VERSION,
+                    version,
+                    extensions: Default::default(),
                 }))
                 .await
                 .unwrap();
@@ -126,14 +367,75 @@ mod tests {
                 panic!("expected ClientHello, got: {:?}", payload);
             }
         });
+    }
+
+    #[tokio::test]
+    async fn test_client_handshake_ok() {
+        let (client_stream, server_stream) = mock_utp_stream_pairs(0);
+
+        let client_pmc = PMC::new(false, client_stream);
+        spawn_server(server_stream, VERSION);
 
         let ctx = client_pmc.create_context();
-        client_handshake(
+        let outcome = client_handshake(ctx, client_hello(Some(vec![0; 30])), None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.version, VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_client_handshake_incompatible_version() {
+        let (client_stream, server_stream) = mock_utp_stream_pairs(0);
+
+        let client_pmc = PMC::new(false, client_stream);
+        spawn_server(
+            server_stream,
+            Version {
+                major: VERSION.major + 1,
+                ..VERSION
+            },
+        );
+
+        let ctx = client_pmc.create_context();
+        let result = client_handshake(ctx, client_hello(None), None, None).await;
+
+        assert!(matches!(
+            result,
+            Err(ProtofishError::Connection(
+                ConnectionError::IncompatibleVersion { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_handshake_timeout() {
+        let (client_stream, server_stream) = mock_utp_stream_pairs(0);
+
+        let client_pmc = PMC::new(false, client_stream);
+        let server_pmc = PMC::new(true, server_stream);
+
+        let ctx = client_pmc.create_context();
+        let result = client_handshake(
             ctx,
-            Some(BytesMut::zeroed(30).freeze()),
-            "example.com".into(),
+            client_hello(None),
+            None,
+            Some(Duration::from_millis(20)),
         )
-        .await
-        .unwrap();
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(ProtofishError::Connection(
+                ConnectionError::HandshakeTimeout(_)
+            ))
+        ));
+
+        let (_, rx) = server_pmc.next_context().await.unwrap();
+        assert!(matches!(rx.read().await.unwrap(), Payload::ClientHello(_)));
+        assert!(matches!(
+            rx.read().await.unwrap(),
+            Payload::Error(error) if matches!(error.error_type, ErrorType::Timeout)
+        ));
     }
 }
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+use crate::schema::{AuthChallenge, AuthResponse};
+
+/// Trait for answering authentication challenges during the handshake.
+///
+/// When the server's authenticator answers the `ClientHello` with an
+/// `AuthChallenge`, [`crate::connect_with`] asks the provider for a response.
+/// The exchange repeats until the server sends its `ServerHello`.
+#[async_trait]
+pub trait CredentialProvider: Send + Sync + 'static {
+    /// Answers a challenge issued by the server.
+    ///
+    /// Returns `None` if none of the offered mechanisms is supported, which
+    /// aborts the handshake.
+    ///
+    /// # Arguments
+    ///
+    /// * `challenge` - The challenge including the mechanisms offered by the server
+    async fn respond(&self, challenge: &AuthChallenge) -> Option<AuthResponse>;
+}
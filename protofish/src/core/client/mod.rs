@@ -1,2 +1,5 @@
 mod client;
 pub use client::*;
+
+mod credential;
+pub use credential::CredentialProvider;
@@ -1,16 +1,26 @@
 use std::sync::Arc;
+#[cfg(feature = "futures")]
+use std::{
+    pin::Pin,
+    task::{self, Poll, ready},
+};
 
 use bytes::Bytes;
+#[cfg(feature = "futures")]
+use futures_util::{Sink, Stream};
 use thiserror::Error;
+use tokio::sync::{Mutex, MutexGuard};
 
 use crate::{
     IntegrityType, StreamCreateMeta, StreamOpen,
     core::common::{
-        context::{Context, ContextReader, ContextWriter},
+        channel::ProtofishChannel,
+        codec::CodecError,
+        context::{Context, ContextReader, ContextWriter, WeakContextWriter},
         error::ConnectionError,
         stream::ProtofishStream,
     },
-    schema::{ArbitaryData, Payload},
+    schema::{ArbitaryData, Error as ErrorPayload, ErrorType, Payload, StreamId},
     utp::{UTP, UTPStream, error::UTPError},
 };
 
@@ -18,10 +28,86 @@ use crate::{
 ///
 /// This type provides a simplified interface for sending and receiving
 /// arbitrary binary data through a Protofish context.
+///
+/// [`ArbContext::split`] separates it into an [`ArbSender`] and an
+/// [`ArbReceiver`], e.g. to write and read from different tasks.
 pub struct ArbContext<U: UTP> {
+    sender: ArbSender<U>,
+    receiver: ArbReceiver<U>,
+}
+
+/// Sending half of an [`ArbContext`].
+///
+/// Clones send on the same context.
+pub struct ArbSender<U: UTP> {
     writer: ContextWriter<U::Stream>,
+    utp: Arc<U>,
+}
+
+/// Receiving half of an [`ArbContext`].
+///
+/// It does not keep the context open, so the peer sees the end of the
+/// context once [`ArbSender::close`] is called or all senders and accepted
+/// streams are dropped. Streams opened by the peer after that cannot be
+/// accepted.
+pub struct ArbReceiver<U: UTP> {
     reader: ContextReader,
+    writer: WeakContextWriter<U::Stream>,
     utp: Arc<U>,
+
+    /// Payload received but not consumed yet, e.g. after a peek
+    pending: Mutex<Option<Payload>>,
+}
+
+/// Lock on the pending payload of an [`ArbReceiver`], held from receiving a
+/// payload until it is consumed or put back, so concurrent receives neither
+/// lose nor reorder payloads.
+type Pending<'a> = MutexGuard<'a, Option<Payload>>;
+
+/// Error returned by [`ArbContext::reunite`] when the halves belong to
+/// different contexts, handing them back.
+pub struct ReuniteError<U: UTP>(pub ArbSender<U>, pub ArbReceiver<U>);
+
+impl<U: UTP> std::fmt::Debug for ReuniteError<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ReuniteError").finish_non_exhaustive()
+    }
+}
+
+impl<U: UTP> std::fmt::Display for ReuniteError<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tried to reunite halves of different contexts")
+    }
+}
+
+impl<U: UTP> std::error::Error for ReuniteError<U> {}
+
+/// What the peer did on an arbitrary data context.
+pub enum ArbEvent<U: UTP> {
+    /// The peer wrote arbitrary data
+    Data(Bytes),
+
+    /// The peer opened a stream
+    StreamOpened(ProtofishStream<U::Stream>),
+
+    /// The peer closed the writing half of a stream
+    StreamClosed(StreamId),
+
+    /// The peer failed the context
+    RemoteError { kind: ErrorType, message: String },
+
+    /// The peer ended the context
+    End,
+}
+
+/// Kind of the next [`ArbEvent`], as returned by [`ArbContext::peek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbEventKind {
+    Data,
+    StreamOpened,
+    StreamClosed,
+    RemoteError,
+    End,
 }
 
 /// Errors that can occur during arbitrary data operations.
@@ -35,12 +121,179 @@ pub enum ArbError {
     #[error("unexpected data: {0}")]
     UnexpectedData(String),
 
+    /// The peer failed the context with an `Error` payload
+    #[error("remote error ({kind}): {message}")]
+    Remote { kind: ErrorType, message: String },
+
+    /// A typed message could not be encoded or decoded
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
+
     /// UTP Error
     #[error("UTP error: {0}")]
     UTP(#[from] UTPError),
 }
 
+impl ArbError {
+    /// Maps a payload other than the `expected` one to an error, keeping the
+    /// peer's own error if it sent one.
+    pub(crate) fn unexpected(payload: Payload, expected: &str) -> Self {
+        match payload {
+            Payload::Error(error) => ArbError::Remote {
+                kind: error.error_type,
+                message: error.message,
+            },
+            _ => ArbError::UnexpectedData(format!("expected {}", expected)),
+        }
+    }
+}
+
+impl<U: UTP> ArbContext<U> {
+    /// Writes arbitrary binary data to this context.
+    ///
+    /// See [`ArbSender::write`].
+    pub async fn write(&self, content: Bytes) -> Result<(), ArbError> {
+        self.sender.write(content).await
+    }
+
+    /// Reads arbitrary binary data from this context.
+    ///
+    /// See [`ArbReceiver::read`].
+    pub async fn read(&self) -> Result<Bytes, ArbError> {
+        self.receiver.read().await
+    }
+
+    /// Reports a failure to the peer.
+    ///
+    /// See [`ArbSender::fail`].
+    pub async fn fail(
+        &self,
+        error_type: ErrorType,
+        message: impl Into<String>,
+    ) -> Result<(), ArbError> {
+        self.sender.fail(error_type, message).await
+    }
+
+    /// Ends this context.
+    ///
+    /// See [`ArbSender::close`].
+    pub async fn close(&self) -> Result<(), ArbError> {
+        self.sender.close().await
+    }
+
+    /// Waits for the peer to open a stream.
+    ///
+    /// See [`ArbReceiver::wait_stream`].
+    pub async fn wait_stream(&self) -> Result<ProtofishStream<U::Stream>, ArbError> {
+        self.receiver.wait_stream().await
+    }
+
+    /// Waits for whatever the peer does next on this context.
+    ///
+    /// See [`ArbReceiver::next_event`].
+    pub async fn next_event(&self) -> Result<ArbEvent<U>, ArbError> {
+        self.receiver.next_event().await
+    }
+
+    /// Returns the kind of the next event without consuming it.
+    ///
+    /// See [`ArbReceiver::peek`].
+    pub async fn peek(&self) -> Result<ArbEventKind, ArbError> {
+        self.receiver.peek().await
+    }
+
+    /// Opens a stream and announces it to the peer.
+    ///
+    /// See [`ArbSender::new_stream`].
+    pub async fn new_stream(
+        &self,
+        integrity: IntegrityType,
+    ) -> Result<ProtofishStream<U::Stream>, ArbError> {
+        self.sender.new_stream(integrity).await
+    }
+
+    /// Opens an unreliable channel and announces it to the peer.
+    ///
+    /// See [`ArbSender::new_channel`].
+    pub async fn new_channel(&self) -> Result<ProtofishChannel<U::Stream>, ArbError> {
+        self.sender.new_channel().await
+    }
+
+    /// Waits for the peer to open an unreliable channel.
+    ///
+    /// See [`ArbReceiver::wait_channel`].
+    pub async fn wait_channel(&self) -> Result<ProtofishChannel<U::Stream>, ArbError> {
+        self.receiver.wait_channel().await
+    }
+
+    /// Splits this context into its sending and receiving halves.
+    pub fn split(self) -> (ArbSender<U>, ArbReceiver<U>) {
+        (self.sender, self.receiver)
+    }
+
+    /// Puts the halves returned by [`ArbContext::split`] back together.
+    ///
+    /// # Errors
+    ///
+    /// Returns the halves in a [`ReuniteError`] if they belong to different
+    /// contexts.
+    pub fn reunite(
+        sender: ArbSender<U>,
+        receiver: ArbReceiver<U>,
+    ) -> Result<Self, Box<ReuniteError<U>>> {
+        if receiver.writer.same_context(&sender.writer) {
+            Ok(Self { sender, receiver })
+        } else {
+            Err(Box::new(ReuniteError(sender, receiver)))
+        }
+    }
+}
+
 impl<U: UTP> ArbContext<U> {
+    /// Sends a raw payload on this context.
+    pub(crate) async fn send(&self, payload: Payload) -> Result<(), ConnectionError> {
+        self.sender.send(payload).await
+    }
+
+    /// Returns the writer and reader of this context, the inverse of
+    /// [`make_arbitrary`].
+    ///
+    /// A peeked payload is handed back as the first one of the reader.
+    pub fn into_parts(self) -> Context<U::Stream> {
+        let ArbReceiver {
+            reader,
+            mut pending,
+            ..
+        } = self.receiver;
+
+        // a payload is only pending once the reader handed out its first one
+        if let Some(payload) = pending.get_mut().take() {
+            *reader.first.lock() = Some(payload);
+        }
+
+        (self.sender.writer, reader)
+    }
+
+    /// Receives the next raw payload of this context.
+    ///
+    /// See [`ArbReceiver::recv`].
+    pub(crate) async fn recv(&self) -> Result<Payload, ConnectionError> {
+        let (payload, _) = self.receiver.recv().await?;
+
+        Ok(payload)
+    }
+}
+
+impl<U: UTP> Clone for ArbSender<U> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            utp: self.utp.clone(),
+        }
+    }
+}
+
+impl<U: UTP> ArbSender<U> {
     /// Writes arbitrary binary data to this context.
     ///
     /// The bytes will be wrapped in an `ArbitaryData` payload and sent
@@ -59,6 +312,103 @@ impl<U: UTP> ArbContext<U> {
         Ok(())
     }
 
+    /// Reports a failure to the peer.
+    ///
+    /// The peer's next [`ArbReceiver::read`] returns `ArbError::Remote` with
+    /// the given kind and message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying write operation fails.
+    pub async fn fail(
+        &self,
+        error_type: ErrorType,
+        message: impl Into<String>,
+    ) -> Result<(), ArbError> {
+        let payload = Payload::Error(ErrorPayload {
+            error_type,
+            message: message.into(),
+        });
+
+        self.writer.write(payload).await?;
+
+        Ok(())
+    }
+
+    /// Ends this context.
+    ///
+    /// The peer reads the payloads written before, then
+    /// `ConnectionError::ContextEnded`. Dropping the context and its streams
+    /// ends it as well.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context was already closed or the underlying
+    /// write operation fails.
+    pub async fn close(&self) -> Result<(), ArbError> {
+        self.writer.close().await?;
+
+        Ok(())
+    }
+
+    /// Opens a stream and announces it to the peer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the UTP cannot open the stream or the announcement
+    /// cannot be written.
+    pub async fn new_stream(
+        &self,
+        integrity: IntegrityType,
+    ) -> Result<ProtofishStream<U::Stream>, ArbError> {
+        let stream = self.utp.new_stream(integrity.clone()).await?;
+        self.writer
+            .write(Payload::StreamOpen(StreamOpen {
+                stream_id: stream.id(),
+                meta: StreamCreateMeta {
+                    integrity_type: integrity,
+                },
+            }))
+            .await?;
+        Ok(ProtofishStream::new(stream, self.writer.clone()))
+    }
+
+    /// Opens an unreliable channel and announces it to the peer.
+    ///
+    /// The channel carries whole messages, see [`ProtofishChannel`]; the
+    /// peer takes it with [`ArbReceiver::wait_channel`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the UTP cannot open an unreliable stream or turn
+    /// it into a channel, or the announcement cannot be written.
+    pub async fn new_channel(&self) -> Result<ProtofishChannel<U::Stream>, ArbError> {
+        let stream = self.utp.new_stream(IntegrityType::Unreliable).await?;
+        let stream_id = stream.id();
+        let channel = stream.into_channel()?;
+
+        self.writer
+            .write(Payload::StreamOpen(StreamOpen {
+                stream_id,
+                meta: StreamCreateMeta {
+                    integrity_type: IntegrityType::Unreliable,
+                },
+            }))
+            .await?;
+        Ok(ProtofishChannel::new(
+            stream_id,
+            channel,
+            self.writer.clone(),
+        ))
+    }
+
+    /// Sends a raw payload on this context.
+    pub(crate) async fn send(&self, payload: Payload) -> Result<(), ConnectionError> {
+        self.writer.write(payload).await
+    }
+}
+
+impl<U: UTP> ArbReceiver<U> {
     /// Reads arbitrary binary data from this context.
     ///
     /// This method expects the next payload to be `ArbitaryData` and
@@ -71,45 +421,322 @@ impl<U: UTP> ArbContext<U> {
     ///
     /// # Errors
     ///
-    /// Returns `ArbError::UnexpectedData` if a non-`ArbitaryData` payload
+    /// Returns `ArbError::Remote` if the peer failed the context,
+    /// `ArbError::UnexpectedData` if another non-`ArbitaryData` payload
     /// is received, or `ArbError::Connection` if the read fails.
+    ///
+    /// A `StreamOpen` is kept for the next [`ArbReceiver::wait_stream`] or
+    /// [`ArbReceiver::next_event`].
     pub async fn read(&self) -> Result<Bytes, ArbError> {
-        let data_got = self.reader.read().await?;
+        let (payload, mut pending) = self.recv().await?;
 
-        if let Payload::ArbitaryData(data) = data_got {
-            Ok(Bytes::from(data.content))
-        } else {
-            Err(ArbError::UnexpectedData("expected ArbitaryData".into()))
+        match payload {
+            Payload::ArbitaryData(data) => Ok(Bytes::from(data.content)),
+            Payload::StreamOpen(open) => {
+                *pending = Some(Payload::StreamOpen(open));
+                Err(ArbError::UnexpectedData(
+                    "expected ArbitaryData, the peer opened a stream".into(),
+                ))
+            }
+            payload => Err(ArbError::unexpected(payload, "ArbitaryData")),
         }
     }
 
+    /// Waits for the peer to open a stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArbError::Remote` if the peer failed the context,
+    /// `ArbError::UnexpectedData` if another payload is received, or an
+    /// error if the stream cannot be accepted.
+    ///
+    /// Arbitrary data is kept for the next [`ArbReceiver::read`] or
+    /// [`ArbReceiver::next_event`].
     pub async fn wait_stream(&self) -> Result<ProtofishStream<U::Stream>, ArbError> {
-        let data_got = self.reader.read().await?;
-
-        if let Payload::StreamOpen(meta) = data_got {
-            let utp_stream = self
-                .utp
-                .wait_stream(meta.stream_id, meta.meta.integrity_type)
-                .await?;
-            Ok(ProtofishStream::new(utp_stream))
-        } else {
-            Err(ArbError::UnexpectedData("expected ArbitaryData".into()))
+        let (stream, writer) = self.next_stream().await?;
+
+        Ok(ProtofishStream::new(stream, writer))
+    }
+
+    /// Waits for whatever the peer does next on this context.
+    ///
+    /// Unlike [`ArbReceiver::read`] and [`ArbReceiver::wait_stream`], this
+    /// accepts data and streams alike, so the caller does not need to know
+    /// the order in advance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArbError::UnexpectedData` for payloads that are not part of
+    /// an arbitrary data conversation, or an error if the read fails or an
+    /// opened stream cannot be accepted.
+    pub async fn next_event(&self) -> Result<ArbEvent<U>, ArbError> {
+        let payload = match self.recv_any().await {
+            Ok((payload, _)) => payload,
+            Err(ConnectionError::ContextEnded) => return Ok(ArbEvent::End),
+            Err(e) => return Err(e.into()),
+        };
+
+        match payload {
+            Payload::ArbitaryData(data) => Ok(ArbEvent::Data(Bytes::from(data.content))),
+            Payload::StreamOpen(open) => {
+                Ok(ArbEvent::StreamOpened(self.accept_stream(open).await?))
+            }
+            Payload::StreamClose(close) => Ok(ArbEvent::StreamClosed(close.stream_id)),
+            Payload::Error(error) => Ok(ArbEvent::RemoteError {
+                kind: error.error_type,
+                message: error.message,
+            }),
+            payload => Err(ArbError::unexpected(payload, "an arbitrary data event")),
         }
     }
-    pub async fn new_stream(
+
+    /// Returns the kind of the next event without consuming it.
+    ///
+    /// The payload is kept for the next [`ArbReceiver::next_event`],
+    /// [`ArbReceiver::read`] or [`ArbReceiver::wait_stream`]; a peeked stream
+    /// is only accepted once it is taken.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`ArbReceiver::next_event`]; unexpected payloads are
+    /// consumed.
+    pub async fn peek(&self) -> Result<ArbEventKind, ArbError> {
+        let (payload, mut pending) = match self.recv_any().await {
+            Ok(received) => received,
+            Err(ConnectionError::ContextEnded) => return Ok(ArbEventKind::End),
+            Err(e) => return Err(e.into()),
+        };
+
+        let kind = match &payload {
+            Payload::ArbitaryData(_) => ArbEventKind::Data,
+            Payload::StreamOpen(_) => ArbEventKind::StreamOpened,
+            Payload::StreamClose(_) => ArbEventKind::StreamClosed,
+            Payload::Error(_) => ArbEventKind::RemoteError,
+            _ => return Err(ArbError::unexpected(payload, "an arbitrary data event")),
+        };
+
+        *pending = Some(payload);
+
+        Ok(kind)
+    }
+
+    /// Waits for the peer to open an unreliable channel.
+    ///
+    /// A stream announced by the peer is taken as a channel, so this is the
+    /// counterpart of [`ArbSender::new_channel`]. Use [`ArbReceiver::peek`]
+    /// to tell a channel from data before taking it.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`ArbReceiver::wait_stream`], or with
+    /// `UTPError::ChannelUnsupported` if the stream cannot carry messages.
+    pub async fn wait_channel(&self) -> Result<ProtofishChannel<U::Stream>, ArbError> {
+        let (stream, writer) = self.next_stream().await?;
+        let stream_id = stream.id();
+
+        Ok(ProtofishChannel::new(
+            stream_id,
+            stream.into_channel()?,
+            writer,
+        ))
+    }
+
+    /// Accepts the next stream opened by the peer, for
+    /// [`ArbReceiver::wait_stream`] and [`ArbReceiver::wait_channel`].
+    ///
+    /// Arbitrary data is kept for the next receive.
+    async fn next_stream(&self) -> Result<(U::Stream, ContextWriter<U::Stream>), ArbError> {
+        let (payload, mut pending) = self.recv().await?;
+
+        match payload {
+            Payload::StreamOpen(open) => {
+                drop(pending);
+                self.accept_utp_stream(open).await
+            }
+            Payload::ArbitaryData(data) => {
+                *pending = Some(Payload::ArbitaryData(data));
+                Err(ArbError::UnexpectedData(
+                    "expected StreamOpen, the peer wrote data".into(),
+                ))
+            }
+            payload => Err(ArbError::unexpected(payload, "StreamOpen")),
+        }
+    }
+
+    async fn accept_stream(
         &self,
-        integrity: IntegrityType,
+        open: StreamOpen,
     ) -> Result<ProtofishStream<U::Stream>, ArbError> {
-        let stream = self.utp.new_stream(integrity.clone()).await?;
+        let (stream, writer) = self.accept_utp_stream(open).await?;
+
+        Ok(ProtofishStream::new(stream, writer))
+    }
+
+    /// Returns a writer keeping the context open for an accepted stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionError::ClosedStream` once all senders and streams
+    /// were dropped, which ended the context.
+    fn context(&self) -> Result<ContextWriter<U::Stream>, ArbError> {
         self.writer
-            .write(Payload::StreamOpen(StreamOpen {
-                stream_id: stream.id(),
-                meta: StreamCreateMeta {
-                    integrity_type: integrity,
+            .upgrade()
+            .ok_or(ArbError::Connection(ConnectionError::ClosedStream))
+    }
+
+    /// Accepts the announced stream, with a writer keeping the context open
+    /// for it.
+    async fn accept_utp_stream(
+        &self,
+        open: StreamOpen,
+    ) -> Result<(U::Stream, ContextWriter<U::Stream>), ArbError> {
+        let writer = self.context()?;
+
+        // the stream never arrives once the connection is gone
+        tokio::select! {
+            stream = self.utp.wait_stream(open.stream_id, open.meta.integrity_type) => {
+                Ok((stream?, writer))
+            }
+            error = self.writer.pmc_frame.terminated() => Err(error.into()),
+        }
+    }
+
+    /// Receives the next raw payload of this context.
+    ///
+    /// `StreamClose` is skipped; the UTP already released the stream when it
+    /// arrived.
+    pub(crate) async fn recv(&self) -> Result<(Payload, Pending<'_>), ConnectionError> {
+        loop {
+            match self.recv_any().await? {
+                (Payload::StreamClose(_), _) => continue,
+                received => return Ok(received),
+            }
+        }
+    }
+
+    /// Receives the next raw payload, starting with a pending one.
+    ///
+    /// Storing the payload in the returned lock keeps it for the next
+    /// receive.
+    async fn recv_any(&self) -> Result<(Payload, Pending<'_>), ConnectionError> {
+        let mut pending = self.pending.lock().await;
+        if let Some(payload) = pending.take() {
+            return Ok((payload, pending));
+        }
+
+        let payload = self.reader.read().await?;
+
+        Ok((payload, pending))
+    }
+}
+
+/// Yields the arbitrary data written by the peer, like [`ArbReceiver::read`].
+///
+/// The stream ends with the context. Where the peer opened a stream, it
+/// yields `ArbError::UnexpectedData` and keeps the stream for
+/// [`ArbReceiver::wait_stream`], and continues with the data after it once
+/// the stream is taken. Other payloads are yielded as errors.
+#[cfg(feature = "futures")]
+impl<U: UTP> Stream for ArbReceiver<U> {
+    type Item = Result<Bytes, ArbError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let payload = match this.pending.get_mut().take() {
+                Some(payload) => payload,
+                None => match ready!(Pin::new(&mut this.reader).poll_next(cx)) {
+                    Some(Ok(payload)) => payload,
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                    None => return Poll::Ready(None),
                 },
-            }))
-            .await?;
-        Ok(ProtofishStream::new(stream))
+            };
+
+            match payload {
+                Payload::ArbitaryData(data) => {
+                    return Poll::Ready(Some(Ok(Bytes::from(data.content))));
+                }
+                Payload::StreamClose(_) => continue,
+                Payload::StreamOpen(open) => {
+                    *this.pending.get_mut() = Some(Payload::StreamOpen(open));
+                    return Poll::Ready(Some(Err(ArbError::UnexpectedData(
+                        "expected ArbitaryData, the peer opened a stream".into(),
+                    ))));
+                }
+                payload => {
+                    return Poll::Ready(Some(Err(ArbError::unexpected(payload, "ArbitaryData"))));
+                }
+            }
+        }
+    }
+}
+
+/// Writes arbitrary data to the context, like [`ArbSender::write`].
+///
+/// Closing the sink ends the context, like [`ArbSender::close`].
+#[cfg(feature = "futures")]
+impl<U: UTP> Sink<Bytes> for ArbSender<U> {
+    type Error = ArbError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().writer)
+            .poll_ready(cx)
+            .map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), ArbError> {
+        let payload = Payload::ArbitaryData(ArbitaryData {
+            content: item.into(),
+        });
+
+        Pin::new(&mut self.get_mut().writer)
+            .start_send(payload)
+            .map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().writer)
+            .poll_flush(cx)
+            .map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().writer)
+            .poll_close(cx)
+            .map_err(Into::into)
+    }
+}
+
+/// See the `Stream` implementation of [`ArbReceiver`].
+#[cfg(feature = "futures")]
+impl<U: UTP> Stream for ArbContext<U> {
+    type Item = Result<Bytes, ArbError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().receiver).poll_next(cx)
+    }
+}
+
+/// See the `Sink` implementation of [`ArbSender`].
+#[cfg(feature = "futures")]
+impl<U: UTP> Sink<Bytes> for ArbContext<U> {
+    type Error = ArbError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().sender).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), ArbError> {
+        Pin::new(&mut self.get_mut().sender).start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().sender).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Result<(), ArbError>> {
+        Pin::new(&mut self.get_mut().sender).poll_close(cx)
     }
 }
 
@@ -119,8 +746,12 @@ impl<U: UTP> ArbContext<U> {
 /// arbitrary data interface.
 pub fn make_arbitrary<U: UTP>(utp: Arc<U>, (writer, reader): Context<U::Stream>) -> ArbContext<U> {
     ArbContext {
-        utp,
-        writer,
-        reader,
+        receiver: ArbReceiver {
+            reader,
+            writer: writer.downgrade(),
+            utp: utp.clone(),
+            pending: Mutex::new(None),
+        },
+        sender: ArbSender { writer, utp },
     }
 }
@@ -0,0 +1,198 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::Instant,
+};
+
+use crate::{
+    core::common::{
+        arbitrary::{ArbContext, ArbError, make_arbitrary},
+        context::{ContextReader, ContextWriter},
+        pmc::PMC,
+    },
+    schema::{BenchmarkEnd, BenchmarkStart, IntegrityType, Payload},
+    utp::{UTP, error::UTPError},
+};
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// How long the responder keeps reading an unreliable stream after the client
+/// reported the end of the run, to collect bytes still in flight.
+const UNRELIABLE_GRACE: Duration = Duration::from_millis(100);
+
+/// Outcome of a benchmark run started with
+/// [`Connection::run_benchmark`](crate::Connection::run_benchmark).
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    /// Integrity type of the benchmarked stream
+    pub integrity_type: IntegrityType,
+
+    /// Number of bytes written by the client
+    pub bytes_sent: u64,
+
+    /// Number of bytes the responder read
+    pub bytes_received: u64,
+
+    /// Time from the first byte written until the responder's report arrived
+    pub elapsed: Duration,
+}
+
+impl BenchmarkResult {
+    /// Returns the received bytes per second, or zero if no time elapsed.
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+
+        self.bytes_received as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Returns the number of bytes that did not reach the responder.
+    ///
+    /// This is always zero for reliable runs.
+    pub fn bytes_lost(&self) -> u64 {
+        self.bytes_sent.saturating_sub(self.bytes_received)
+    }
+}
+
+/// Runs the client side of a benchmark on a fresh context.
+///
+/// The client announces the run with `BenchmarkStart`, opens a stream of the
+/// given integrity type, writes `byte_count` bytes and reports the end with
+/// `BenchmarkEnd`. The responder answers with a `BenchmarkEnd` carrying the
+/// number of bytes it read.
+pub(crate) async fn run_benchmark<U: UTP>(
+    arb: ArbContext<U>,
+    integrity_type: IntegrityType,
+    byte_count: u64,
+) -> Result<BenchmarkResult, ArbError> {
+    arb.send(Payload::BenchmarkStart(BenchmarkStart {
+        integrity_type: integrity_type.clone(),
+        byte_count,
+    }))
+    .await?;
+
+    let stream = arb.new_stream(integrity_type.clone()).await?;
+    let (mut writer, _reader) = stream.split();
+
+    let started = Instant::now();
+
+    let chunk = vec![0u8; CHUNK_SIZE];
+    let mut remaining = byte_count;
+    while remaining > 0 {
+        let len = remaining.min(CHUNK_SIZE as u64) as usize;
+        writer
+            .write_all(&chunk[..len])
+            .await
+            .map_err(UTPError::from)?;
+        remaining -= len as u64;
+    }
+    writer.flush().await.map_err(UTPError::from)?;
+
+    arb.send(Payload::BenchmarkEnd(BenchmarkEnd { byte_count }))
+        .await?;
+
+    let bytes_received = match arb.recv().await? {
+        Payload::BenchmarkEnd(end) => end.byte_count,
+        payload => return Err(ArbError::unexpected(payload, "BenchmarkEnd")),
+    };
+
+    Ok(BenchmarkResult {
+        integrity_type,
+        bytes_sent: byte_count,
+        bytes_received,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Answers the benchmark runs the peer opens on `pmc` in the background.
+///
+/// Their contexts never reach [`Connection::next_arb`](crate::Connection::next_arb),
+/// so runs are answered whether or not the application accepts contexts.
+pub(crate) fn spawn_responder<U: UTP>(utp: &Arc<U>, pmc: &PMC<U::Stream>) {
+    let weak_utp = Arc::downgrade(utp);
+    let weak_frame = Arc::downgrade(pmc.frame());
+
+    pmc.frame().on_incoming(move |incoming| {
+        let Payload::BenchmarkStart(start) = &incoming.message.payload else {
+            return Some(incoming);
+        };
+        let (Some(utp), Some(frame)) = (weak_utp.upgrade(), weak_frame.upgrade()) else {
+            return Some(incoming);
+        };
+
+        let start = start.clone();
+        let writer = ContextWriter::new(incoming.message.context_id, frame, &incoming.receiver);
+        let arb = make_arbitrary(utp, (writer, ContextReader::new(None, incoming.receiver)));
+
+        tokio::spawn(async move {
+            if let Err(e) = respond_benchmark(arb, start).await {
+                tracing::warn!("Benchmark run failed: {}", e);
+            }
+        });
+
+        None
+    });
+}
+
+/// Answers a benchmark run announced by the peer with `start`.
+async fn respond_benchmark<U: UTP>(
+    arb: ArbContext<U>,
+    start: BenchmarkStart,
+) -> Result<(), ArbError> {
+    let stream = arb.wait_stream().await?;
+    let (_writer, mut reader) = stream.split();
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut received = 0u64;
+    let mut ended = false;
+    let lossy = matches!(start.integrity_type, IntegrityType::Unreliable);
+
+    while received < start.byte_count {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(len) => received += len as u64,
+            },
+            payload = arb.recv(), if !ended => match payload? {
+                Payload::BenchmarkEnd(_) => ended = true,
+                payload => return Err(ArbError::unexpected(payload, "BenchmarkEnd")),
+            },
+            _ = tokio::time::sleep(UNRELIABLE_GRACE), if ended && lossy => break,
+        }
+    }
+
+    arb.send(Payload::BenchmarkEnd(BenchmarkEnd {
+        byte_count: received,
+    }))
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{core::common::benchmark::BenchmarkResult, schema::IntegrityType};
+
+    #[test]
+    fn test_benchmark_result() {
+        let result = BenchmarkResult {
+            integrity_type: IntegrityType::Unreliable,
+            bytes_sent: 1000,
+            bytes_received: 800,
+            elapsed: Duration::from_secs(2),
+        };
+
+        assert_eq!(result.throughput(), 400.0);
+        assert_eq!(result.bytes_lost(), 200);
+
+        let instant = BenchmarkResult {
+            elapsed: Duration::ZERO,
+            ..result
+        };
+        assert_eq!(instant.throughput(), 0.0);
+    }
+}
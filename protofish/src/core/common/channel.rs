@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::{
+    core::common::{context::ContextWriter, stream::StreamCloser},
+    schema::StreamId,
+    utp::{UTPStream, UnreliableChannel, error::UTPError},
+};
+
+/// An unreliable stream opened on a context, carrying whole messages.
+///
+/// Each message travels as a single datagram, so a lost datagram loses one
+/// message instead of corrupting the ones after it. The peer is told that
+/// the channel ended once it is dropped.
+pub struct ProtofishChannel<U: UTPStream> {
+    channel: U::Channel,
+    closer: StreamCloser<U>,
+}
+
+impl<U: UTPStream> ProtofishChannel<U> {
+    pub(crate) fn new(stream_id: StreamId, channel: U::Channel, context: ContextWriter<U>) -> Self {
+        Self {
+            channel,
+            closer: StreamCloser::new(stream_id, context),
+        }
+    }
+
+    /// Returns the ID of the underlying stream.
+    pub fn id(&self) -> StreamId {
+        self.closer.stream_id
+    }
+
+    /// Returns the writer of the context this channel was opened on.
+    pub fn context(&self) -> &ContextWriter<U> {
+        &self.closer.context
+    }
+}
+
+#[async_trait]
+impl<U: UTPStream> UnreliableChannel for ProtofishChannel<U> {
+    fn max_message_size(&self) -> usize {
+        self.channel.max_message_size()
+    }
+
+    async fn send(&self, message: Bytes) -> Result<(), UTPError> {
+        self.channel.send(message).await
+    }
+
+    async fn recv(&self) -> Result<Bytes, UTPError> {
+        self.channel.recv().await
+    }
+}
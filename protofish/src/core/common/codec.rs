@@ -0,0 +1,152 @@
+//! Encodings of typed messages, see [`crate::TypedContext`].
+//!
+//! Each built-in codec is behind a cargo feature: [`ProstCodec`] behind
+//! `codec-prost`, [`JsonCodec`] behind `codec-json` and [`PostcardCodec`]
+//! behind `codec-postcard`.
+
+use bytes::Bytes;
+use thiserror::Error;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors that can occur while encoding or decoding a typed message.
+#[derive(Debug, Error)]
+pub enum CodecError {
+    /// The value could not be encoded
+    #[error("failed to encode message: {0}")]
+    Encode(BoxError),
+
+    /// The received bytes are not a valid message
+    #[error("failed to decode message: {0}")]
+    Decode(BoxError),
+}
+
+/// Converts messages of type `T` to and from bytes.
+///
+/// Codecs are stateless marker types, so both sides of a context only need
+/// to agree on the type.
+pub trait Codec<T> {
+    fn encode(value: &T) -> Result<Bytes, CodecError>;
+
+    fn decode(buf: Bytes) -> Result<T, CodecError>;
+}
+
+/// Encodes protobuf messages generated by `prost`.
+#[cfg(feature = "codec-prost")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProstCodec;
+
+#[cfg(feature = "codec-prost")]
+impl<T: prost::Message + Default> Codec<T> for ProstCodec {
+    fn encode(value: &T) -> Result<Bytes, CodecError> {
+        Ok(value.encode_to_vec().into())
+    }
+
+    fn decode(buf: Bytes) -> Result<T, CodecError> {
+        T::decode(buf).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+/// Encodes `serde` values as JSON.
+#[cfg(feature = "codec-json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "codec-json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(value: &T) -> Result<Bytes, CodecError> {
+        serde_json::to_vec(value)
+            .map(Bytes::from)
+            .map_err(|e| CodecError::Encode(e.into()))
+    }
+
+    fn decode(buf: Bytes) -> Result<T, CodecError> {
+        serde_json::from_slice(&buf).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+/// Encodes `serde` values in the compact binary format of `postcard`.
+#[cfg(feature = "codec-postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+#[cfg(feature = "codec-postcard")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for PostcardCodec {
+    fn encode(value: &T) -> Result<Bytes, CodecError> {
+        postcard::to_allocvec(value)
+            .map(Bytes::from)
+            .map_err(|e| CodecError::Encode(e.into()))
+    }
+
+    fn decode(buf: Bytes) -> Result<T, CodecError> {
+        postcard::from_bytes(&buf).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "codec-prost",
+        feature = "codec-json",
+        feature = "codec-postcard"
+    )
+))]
+mod tests {
+    use super::*;
+
+    #[cfg(any(feature = "codec-json", feature = "codec-postcard"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Track {
+        title: String,
+        length: u32,
+    }
+
+    #[cfg(any(feature = "codec-json", feature = "codec-postcard"))]
+    fn track() -> Track {
+        Track {
+            title: "Kirameki".into(),
+            length: 241,
+        }
+    }
+
+    #[cfg(feature = "codec-prost")]
+    #[test]
+    fn test_prost_codec() {
+        let value = prost_types::Duration {
+            seconds: 3,
+            nanos: 500,
+        };
+
+        let buf = ProstCodec::encode(&value).unwrap();
+        let decoded: prost_types::Duration = ProstCodec::decode(buf).unwrap();
+        assert_eq!(decoded, value);
+
+        let invalid =
+            <ProstCodec as Codec<prost_types::Duration>>::decode(Bytes::from_static(&[0xff]));
+        assert!(matches!(invalid, Err(CodecError::Decode(_))));
+    }
+
+    #[cfg(feature = "codec-json")]
+    #[test]
+    fn test_json_codec() {
+        let buf = JsonCodec::encode(&track()).unwrap();
+        assert_eq!(buf, r#"{"title":"Kirameki","length":241}"#);
+
+        let decoded: Track = JsonCodec::decode(buf).unwrap();
+        assert_eq!(decoded, track());
+
+        let invalid = <JsonCodec as Codec<Track>>::decode(Bytes::from_static(b"{}"));
+        assert!(matches!(invalid, Err(CodecError::Decode(_))));
+    }
+
+    #[cfg(feature = "codec-postcard")]
+    #[test]
+    fn test_postcard_codec() {
+        let buf = PostcardCodec::encode(&track()).unwrap();
+        let decoded: Track = PostcardCodec::decode(buf).unwrap();
+        assert_eq!(decoded, track());
+
+        let invalid = <PostcardCodec as Codec<Track>>::decode(Bytes::new());
+        assert!(matches!(invalid, Err(CodecError::Decode(_))));
+    }
+}
@@ -1,13 +1,59 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
 
 use crate::{
-    core::common::{
-        arbitrary::{ArbContext, make_arbitrary},
-        pmc::PMC,
+    constant::{CONTROL_CONTEXT_ID, DEFAULT_DRAIN_TIMEOUT, VERSION},
+    core::{
+        common::{
+            arbitrary::{ArbContext, ArbError, make_arbitrary},
+            benchmark::{BenchmarkResult, run_benchmark},
+            codec::Codec,
+            counter::ContextCounter,
+            error::ConnectionError,
+            keepalive::{Heartbeat, KeepaliveOptions},
+            pmc::PMC,
+            typed::TypedContext,
+        },
+        server::Principal,
     },
+    schema::{Close, Extensions, IntegrityType, Message, Payload, Version},
     utp::UTP,
 };
 
+/// Parameters agreed on during the handshake.
+pub(crate) struct HandshakeOutcome {
+    pub token: Bytes,
+    pub version: Version,
+    pub extensions: Extensions,
+    pub principal: Option<Principal>,
+}
+
+/// Token for resuming a connection with [`resume`](crate::resume).
+///
+/// Besides the connection token issued by the server, it carries the context
+/// ID counter of the original connection so that context IDs continue where
+/// they stopped.
+#[derive(Clone)]
+pub struct ResumeToken {
+    pub(crate) token: Bytes,
+    pub(crate) counter: Arc<Mutex<ContextCounter>>,
+}
+
+impl ResumeToken {
+    /// Returns the connection token issued by the server.
+    pub fn token(&self) -> &Bytes {
+        &self.token
+    }
+}
+
 /// Represents an established Protofish connection.
 ///
 /// A `Connection` provides access to the Primary Messaging Channel (PMC) and
@@ -18,6 +64,12 @@ where
     U: UTP,
 {
     utp: Arc<U>,
+    token: Bytes,
+    version: Version,
+    extensions: Extensions,
+    principal: Option<Principal>,
+    heartbeat: Option<Heartbeat>,
+    closing: AtomicBool,
 
     /// The Primary Messaging Channel for this connection
     pub pmc: PMC<U::Stream>,
@@ -27,8 +79,110 @@ impl<U> Connection<U>
 where
     U: UTP,
 {
+    /// Creates a connection over an established PMC without a handshake.
+    ///
+    /// The connection has an empty connection token, the current protocol
+    /// version, no extensions and no principal.
     pub fn new(utp: Arc<U>, pmc: PMC<U::Stream>) -> Self {
-        Self { utp, pmc }
+        let outcome = HandshakeOutcome {
+            token: Bytes::new(),
+            version: VERSION,
+            extensions: Extensions::new(),
+            principal: None,
+        };
+
+        Self::from_handshake(utp, pmc, outcome)
+    }
+
+    pub(crate) fn from_handshake(
+        utp: Arc<U>,
+        pmc: PMC<U::Stream>,
+        outcome: HandshakeOutcome,
+    ) -> Self {
+        let weak_utp = Arc::downgrade(&utp);
+        pmc.frame().on_stream_close(move |stream_id| {
+            if let Some(utp) = weak_utp.upgrade() {
+                utp.release_stream(stream_id);
+            }
+        });
+
+        if let Some(max_frame_size) = outcome.extensions.max_frame_size() {
+            pmc.frame().lower_max_frame_size(max_frame_size);
+        }
+
+        Self {
+            utp,
+            token: outcome.token,
+            version: outcome.version,
+            extensions: outcome.extensions,
+            principal: outcome.principal,
+            heartbeat: None,
+            closing: AtomicBool::new(false),
+            pmc,
+        }
+    }
+
+    /// Starts sending keepalives to detect a dead peer.
+    ///
+    /// Keepalives of the peer are echoed regardless of whether this is called.
+    pub(crate) fn start_keepalive(&mut self, options: &KeepaliveOptions) {
+        let interval = self
+            .extensions
+            .keepalive_interval()
+            .unwrap_or(options.interval);
+
+        self.heartbeat = Some(Heartbeat::spawn(&self.pmc, interval, options.max_missed));
+    }
+
+    /// Returns the connection token issued by the server during the handshake.
+    ///
+    /// The token identifies the connection and stays the same across resumptions.
+    pub fn connection_token(&self) -> &Bytes {
+        &self.token
+    }
+
+    /// Returns the protocol version negotiated with the peer.
+    ///
+    /// Both sides share the major version; minor and patch are the lower of
+    /// both peers, so higher layers can enable features based on it.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Returns the set of extensions both peers agreed on.
+    ///
+    /// See [`crate::extension`] for the well-known extensions.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the principal the server's authenticator accepted the client as.
+    ///
+    /// This is `None` on the client side and on servers without an authenticator.
+    pub fn principal(&self) -> Option<&Principal> {
+        self.principal.as_ref()
+    }
+
+    /// Returns the round-trip time measured by the latest answered keepalive.
+    ///
+    /// This is `None` if keepalives are disabled or none was answered yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.heartbeat.as_ref()?.rtt()
+    }
+
+    /// Returns the number of received frames that were skipped because their
+    /// payload type is unknown to this version, or because they could not be
+    /// decoded at all under [`DecodePolicy::Skip`](crate::DecodePolicy::Skip).
+    pub fn skipped_payloads(&self) -> u64 {
+        self.pmc.frame().skipped_payloads()
+    }
+
+    /// Returns a token for resuming this connection after it was lost.
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            token: self.token.clone(),
+            counter: self.pmc.counter(),
+        }
     }
 
     /// Creates a new arbitrary data context for sending messages.
@@ -52,10 +206,151 @@ where
     ///
     /// # Returns
     ///
-    /// Returns `Some(ArbContext)` when a new context arrives, or `None` if
-    /// the connection is closed.
-    pub async fn next_arb(&self) -> Option<ArbContext<U>> {
+    /// Returns the new context, or an error once the connection is closed or
+    /// the peer stopped answering keepalives. The error is
+    /// [`ConnectionError::Decode`] if an undecodable frame ended the
+    /// connection under [`crate::DecodePolicy::Fatal`].
+    pub async fn next_arb(&self) -> Result<ArbContext<U>, ConnectionError> {
         let ctx = self.pmc.next_context().await?;
-        Some(make_arbitrary(self.utp.clone(), ctx))
+        Ok(make_arbitrary(self.utp.clone(), ctx))
+    }
+
+    /// Returns the incoming arbitrary data contexts as a stream.
+    ///
+    /// Each item is the result of [`Connection::next_arb`]. The stream ends
+    /// once the connection is closed by either side, or after yielding why
+    /// it ended otherwise.
+    #[cfg(feature = "futures")]
+    pub fn incoming(
+        &self,
+    ) -> impl futures_util::Stream<Item = Result<ArbContext<U>, ConnectionError>> + Send + '_ {
+        futures_util::stream::unfold(Some(self), |conn| async move {
+            let conn = conn?;
+            match conn.next_arb().await {
+                Ok(arb) => Some((Ok(arb), Some(conn))),
+                Err(ConnectionError::ClosedStream | ConnectionError::PeerClosed(_)) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    /// Creates a new context carrying typed messages.
+    ///
+    /// The context writes `Req` and reads `Resp`, both encoded with the
+    /// codec `C`; see [`TypedContext`].
+    pub fn new_typed<Req, Resp, C>(&self) -> TypedContext<Req, Resp, C, U>
+    where
+        C: Codec<Req> + Codec<Resp>,
+    {
+        TypedContext::new(self.new_arb())
+    }
+
+    /// Waits for the next incoming context and treats it as carrying typed
+    /// messages.
+    ///
+    /// Like [`Connection::new_typed`], the context writes `Req` and reads
+    /// `Resp`, so this side names the types the other way around than the
+    /// peer does.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`Connection::next_arb`].
+    pub async fn next_typed<Req, Resp, C>(
+        &self,
+    ) -> Result<TypedContext<Req, Resp, C, U>, ConnectionError>
+    where
+        C: Codec<Req> + Codec<Resp>,
+    {
+        Ok(TypedContext::new(self.next_arb().await?))
+    }
+
+    /// Measures the throughput of a stream to the peer.
+    ///
+    /// Writes `byte_count` bytes on a new stream of the given integrity type
+    /// and asks the peer how many arrived. The peer must have the benchmark
+    /// responder enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream fails or the peer does not answer with
+    /// `BenchmarkEnd`.
+    pub async fn run_benchmark(
+        &self,
+        integrity_type: IntegrityType,
+        byte_count: u64,
+    ) -> Result<BenchmarkResult, ArbError> {
+        run_benchmark(self.new_arb(), integrity_type, byte_count).await
+    }
+
+    /// Closes the connection gracefully.
+    ///
+    /// This is [`Connection::close_within`] with a drain deadline of 5 seconds.
+    /// Only the first call closes the connection; later calls return `Ok`
+    /// right away.
+    pub async fn close(&self, reason: &str) -> Result<(), ConnectionError> {
+        self.close_within(reason, DEFAULT_DRAIN_TIMEOUT).await
+    }
+
+    /// Closes the connection gracefully, waiting at most `drain_timeout` for
+    /// in-flight payloads.
+    ///
+    /// This function performs the following steps:
+    /// 1. Stops accepting new contexts from the peer
+    /// 2. Sends `Close` with the given reason to the peer
+    /// 3. Waits until every payload being sent is written, or the deadline
+    ///    expires
+    /// 4. Stops the PMC, failing remaining contexts, and closes the UTP
+    ///
+    /// Only the first call closes the connection; later calls return `Ok`
+    /// right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `Close` could not be sent. The connection is torn
+    /// down regardless.
+    pub async fn close_within(
+        &self,
+        reason: &str,
+        drain_timeout: Duration,
+    ) -> Result<(), ConnectionError> {
+        if self.closing.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let frame = self.pmc.frame();
+        frame.stop_accepting();
+
+        let sent = frame
+            .send_frame(Message {
+                context_id: CONTROL_CONTEXT_ID,
+                payload: Payload::Close(Close {
+                    reason: reason.into(),
+                }),
+            })
+            .await;
+
+        if tokio::time::timeout(drain_timeout, frame.drained())
+            .await
+            .is_err()
+        {
+            tracing::warn!("Closing connection with payloads still in flight");
+        }
+
+        frame.shutdown();
+        if let Err(e) = frame.shutdown_write().await {
+            tracing::debug!("Failed to shut down the PMC stream: {}", e);
+        }
+        self.utp.close().await;
+
+        sent
+    }
+
+    /// Waits until the connection ends.
+    ///
+    /// This resolves as soon as the peer sends `Close`, returning its reason,
+    /// or with `None` once the connection ended otherwise, e.g. through
+    /// [`Connection::close`] or a lost transport.
+    pub async fn closed(&self) -> Option<String> {
+        self.pmc.frame().closed().await
     }
 }
@@ -1,11 +1,18 @@
-use std::sync::Arc;
+use std::sync::{
+    Arc, Weak,
+    atomic::{AtomicBool, Ordering},
+};
 
-use tokio::sync::mpsc::UnboundedReceiver;
+#[cfg(feature = "futures")]
+use std::{
+    pin::Pin,
+    task::{self, Poll, ready},
+};
 
 use crate::{
     core::common::error::ConnectionError,
-    internal::pmc_frame::PMCFrame,
-    schema::{ContextId, Message, Payload},
+    internal::pmc_frame::{PMCFrame, Received, Subscription},
+    schema::{Close, ContextId, Message, Payload},
     utp::UTPStream,
 };
 
@@ -14,12 +21,70 @@ use crate::{
 /// Each context has a unique context ID that groups related messages together.
 /// The context system provides strict ordering and grouping guarantees within
 /// each context.
+///
+/// Clones share the context. Once the last clone is dropped, the context is
+/// ended as if [`ContextWriter::close`] was called.
 pub struct ContextWriter<S: UTPStream> {
     pub(crate) context_id: ContextId,
     pub(crate) pmc_frame: Arc<PMCFrame<S>>,
+    open: Arc<OpenContext<S>>,
+
+    /// Write or close in progress through the `Sink` implementation
+    #[cfg(feature = "futures")]
+    sending: parking_lot::Mutex<Option<Sending>>,
+}
+
+#[cfg(feature = "futures")]
+type Sending = Pin<Box<dyn Future<Output = Result<(), ConnectionError>> + Send>>;
+
+/// Ends a context once all of its writers are dropped.
+struct OpenContext<S: UTPStream> {
+    context_id: ContextId,
+    pmc_frame: Arc<PMCFrame<S>>,
+
+    /// Shared with the subscription, which sets it if the context overflows
+    ended: Arc<AtomicBool>,
+}
+
+impl<S: UTPStream> Drop for OpenContext<S> {
+    fn drop(&mut self) {
+        if self.ended.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // queued, so it is sent even when dropped outside of a runtime
+        self.pmc_frame.queue_frame(end_message(self.context_id));
+    }
+}
+
+fn end_message(context_id: ContextId) -> Message {
+    Message {
+        context_id,
+        payload: Payload::Close(Close {
+            reason: String::new(),
+        }),
+    }
 }
 
 impl<S: UTPStream> ContextWriter<S> {
+    pub(crate) fn new(
+        context_id: ContextId,
+        pmc_frame: Arc<PMCFrame<S>>,
+        receiver: &Subscription,
+    ) -> Self {
+        Self {
+            context_id,
+            open: Arc::new(OpenContext {
+                context_id,
+                pmc_frame: pmc_frame.clone(),
+                ended: receiver.local_end(),
+            }),
+            pmc_frame,
+            #[cfg(feature = "futures")]
+            sending: Default::default(),
+        }
+    }
+
     /// Writes a payload to this context.
     ///
     /// The payload will be wrapped in a `Message` with this context's ID
@@ -27,27 +92,132 @@ impl<S: UTPStream> ContextWriter<S> {
     ///
     /// # Errors
     ///
-    /// Returns an error if the underlying stream write fails.
+    /// Returns `ConnectionError::ClosedStream` if the context was closed,
+    /// `ConnectionError::FrameTooLarge` if the payload exceeds the maximum
+    /// frame size, or why the connection ended, e.g.
+    /// `ConnectionError::PeerClosed` or `ConnectionError::Transport`.
     pub async fn write(&self, payload: Payload) -> Result<(), ConnectionError> {
+        if self.open.ended.load(Ordering::Acquire) {
+            return Err(ConnectionError::ClosedStream);
+        }
+
         self.pmc_frame
             .send_frame(Message {
                 context_id: self.context_id,
                 payload,
             })
             .await
-            .map_err(ConnectionError::UTP)
+    }
+
+    /// Queues a payload to this context without waiting for it to be
+    /// written, e.g. from `Drop`.
+    pub(crate) fn queue(&self, payload: Payload) {
+        if self.open.ended.load(Ordering::Acquire) {
+            return;
+        }
+
+        self.pmc_frame.queue_frame(Message {
+            context_id: self.context_id,
+            payload,
+        });
+    }
+
+    /// Ends this context.
+    ///
+    /// The peer's reader sees the end of the context after all payloads
+    /// written before. Later writes on any clone fail.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionError::ClosedStream` if the context was already
+    /// closed, or an error if the underlying stream write fails.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        if self.open.ended.swap(true, Ordering::AcqRel) {
+            return Err(ConnectionError::ClosedStream);
+        }
+
+        self.pmc_frame
+            .send_frame(end_message(self.context_id))
+            .await
+    }
+}
+
+impl<S: UTPStream> ContextWriter<S> {
+    /// Returns a handle to this context that does not keep it open.
+    pub(crate) fn downgrade(&self) -> WeakContextWriter<S> {
+        WeakContextWriter {
+            context_id: self.context_id,
+            pmc_frame: self.pmc_frame.clone(),
+            open: Arc::downgrade(&self.open),
+        }
+    }
+}
+
+/// Handle to a context that does not keep it open, see
+/// [`ContextWriter::downgrade`].
+pub(crate) struct WeakContextWriter<S: UTPStream> {
+    pub(crate) context_id: ContextId,
+    pub(crate) pmc_frame: Arc<PMCFrame<S>>,
+    open: Weak<OpenContext<S>>,
+}
+
+impl<S: UTPStream> WeakContextWriter<S> {
+    /// Returns a writer of the context, unless all writers were dropped and
+    /// the context ended.
+    pub(crate) fn upgrade(&self) -> Option<ContextWriter<S>> {
+        Some(ContextWriter {
+            context_id: self.context_id,
+            pmc_frame: self.pmc_frame.clone(),
+            open: self.open.upgrade()?,
+            #[cfg(feature = "futures")]
+            sending: Default::default(),
+        })
+    }
+
+    /// Returns whether `writer` belongs to this context.
+    pub(crate) fn same_context(&self, writer: &ContextWriter<S>) -> bool {
+        std::ptr::eq(self.open.as_ptr(), Arc::as_ptr(&writer.open))
+    }
+}
+
+impl<S: UTPStream> Clone for ContextWriter<S> {
+    fn clone(&self) -> Self {
+        Self {
+            context_id: self.context_id,
+            pmc_frame: self.pmc_frame.clone(),
+            open: self.open.clone(),
+            #[cfg(feature = "futures")]
+            sending: Default::default(),
+        }
     }
 }
 
 /// Reader half of a context, used to receive payloads within a specific context.
 ///
-/// Messages received on this context are delivered in order via an unbounded
-/// channel.
+/// Messages received on this context are delivered in order via a bounded
+/// queue, see [`crate::QueueOptions`]. Dropping the reader unsubscribes the
+/// context.
 pub struct ContextReader {
-    pub(crate) receiver: tokio::sync::Mutex<UnboundedReceiver<Payload>>,
+    pub(crate) first: parking_lot::Mutex<Option<Payload>>,
+    pub(crate) receiver: tokio::sync::Mutex<Subscription>,
+    ended: AtomicBool,
+
+    /// Whether the `Stream` implementation yielded why the connection ended
+    #[cfg(feature = "futures")]
+    exhausted: bool,
 }
 
 impl ContextReader {
+    pub(crate) fn new(first: Option<Payload>, receiver: Subscription) -> Self {
+        Self {
+            first: first.into(),
+            receiver: receiver.into(),
+            ended: AtomicBool::new(false),
+            #[cfg(feature = "futures")]
+            exhausted: false,
+        }
+    }
+
     /// Reads the next payload from this context.
     ///
     /// This method blocks until a message arrives on this context.
@@ -58,14 +228,43 @@ impl ContextReader {
     ///
     /// # Errors
     ///
-    /// Returns `ConnectionError::ClosedStream` if the context channel is closed.
+    /// Returns `ConnectionError::ContextEnded` once the peer ended the
+    /// context, `ConnectionError::ContextOverflow` if payloads were dropped
+    /// because the queue of the context was full, `ConnectionError::Decode`
+    /// if a payload of the context could not be decoded, which ends it, or
+    /// why the connection ended: `ConnectionError::PeerClosed`,
+    /// `ConnectionError::PeerError`, `ConnectionError::Transport`,
+    /// `ConnectionError::ProtocolViolation`, `ConnectionError::Decode`,
+    /// `ConnectionError::PeerUnresponsive`, or `ConnectionError::ClosedStream`
+    /// if it was closed locally.
     pub async fn read(&self) -> Result<Payload, ConnectionError> {
-        self.receiver
-            .lock()
-            .await
-            .recv()
-            .await
-            .ok_or(ConnectionError::ClosedStream)
+        if self.ended.load(Ordering::Acquire) {
+            return Err(ConnectionError::ContextEnded);
+        }
+
+        if let Some(payload) = self.first.lock().take() {
+            return Ok(payload);
+        }
+
+        let received = self.receiver.lock().await.recv().await;
+
+        self.received(received)
+    }
+
+    fn received(&self, received: Received) -> Result<Payload, ConnectionError> {
+        match received {
+            Received::Payload(payload) => Ok(payload),
+            Received::Ended => {
+                self.ended.store(true, Ordering::Release);
+                Err(ConnectionError::ContextEnded)
+            }
+            Received::Overflowed(dropped) => Err(ConnectionError::ContextOverflow(dropped)),
+            Received::Malformed(error) => {
+                self.ended.store(true, Ordering::Release);
+                Err(ConnectionError::Decode(error))
+            }
+            Received::Closed(error) => Err(error),
+        }
     }
 }
 
@@ -74,3 +273,94 @@ impl ContextReader {
 /// Contexts provide strict grouping and ordering of messages, enabling
 /// conversational patterns in communication.
 pub type Context<S> = (ContextWriter<S>, ContextReader);
+
+/// Yields the payloads of the context, like [`ContextReader::read`].
+///
+/// The stream ends with the context. Errors are yielded as they occur; once
+/// the connection ended, the stream ends after yielding why.
+#[cfg(feature = "futures")]
+impl futures_util::Stream for ContextReader {
+    type Item = Result<Payload, ConnectionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.exhausted || this.ended.load(Ordering::Acquire) {
+            return Poll::Ready(None);
+        }
+
+        if let Some(payload) = this.first.get_mut().take() {
+            return Poll::Ready(Some(Ok(payload)));
+        }
+
+        let received = ready!(this.receiver.get_mut().poll_recv(cx));
+        this.exhausted = matches!(received, Received::Closed(_));
+
+        match this.received(received) {
+            Err(ConnectionError::ContextEnded) => Poll::Ready(None),
+            result => Poll::Ready(Some(result)),
+        }
+    }
+}
+
+/// Writes payloads to the context, like [`ContextWriter::write`].
+///
+/// Closing the sink ends the context, like [`ContextWriter::close`], unless
+/// it was already closed. Each clone of the writer is a sink of its own.
+#[cfg(feature = "futures")]
+impl<S: UTPStream> futures_util::Sink<Payload> for ContextWriter<S> {
+    type Error = ConnectionError;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_sending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Payload) -> Result<(), Self::Error> {
+        let writer = self.clone();
+        *self.sending.lock() = Some(Box::pin(async move { writer.write(item).await }));
+
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_sending(cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_sending(cx))?;
+
+        if this.open.ended.load(Ordering::Acquire) {
+            return Poll::Ready(Ok(()));
+        }
+
+        let writer = this.clone();
+        *this.sending.lock() = Some(Box::pin(async move { writer.close().await }));
+
+        this.poll_sending(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: UTPStream> ContextWriter<S> {
+    /// Drives the write or close started through the `Sink` implementation.
+    fn poll_sending(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), ConnectionError>> {
+        let sending = self.sending.get_mut();
+        let Some(future) = sending else {
+            return Poll::Ready(Ok(()));
+        };
+
+        let result = ready!(future.as_mut().poll(cx));
+        *sending = None;
+
+        Poll::Ready(result)
+    }
+}
@@ -0,0 +1,20 @@
+/// What happens to a frame received from the peer that cannot be decoded.
+///
+/// Payloads of types unknown to this version are always skipped, see
+/// [`crate::Connection::skipped_payloads`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodePolicy {
+    /// Drops the frame and keeps receiving.
+    ///
+    /// A malformed payload fails its context, and the peer receives an
+    /// `Error` of type `Malformed` on it. A frame without a decodable
+    /// context is counted in [`crate::Connection::skipped_payloads`].
+    #[default]
+    Skip,
+
+    /// Ends the connection.
+    ///
+    /// The peer receives an `Error` of type `Malformed`, and all pending and
+    /// later reads fail with `ConnectionError::Decode`.
+    Fatal,
+}
@@ -1,6 +1,11 @@
+use std::sync::Arc;
+
 use thiserror::Error;
 
-use crate::{schema::Payload, utp::error::UTPError};
+use crate::{
+    schema::{DecodeError, ErrorType, Payload, Version},
+    utp::error::UTPError,
+};
 
 /// Errors that can occur during Protofish connection operations.
 #[derive(Error, Debug)]
@@ -13,15 +18,59 @@ pub enum ConnectionError {
     #[error("stream closed")]
     ClosedStream,
 
+    /// The peer ended the context
+    #[error("context ended by the peer")]
+    ContextEnded,
+
+    /// Payloads of the context were dropped because its queue was full
+    #[error("context queue overflowed, {0} payloads dropped")]
+    ContextOverflow(u64),
+
+    /// The peer closed the connection, with the reason it gave if any
+    #[error("connection closed by the peer: {}", .0.as_deref().unwrap_or("no reason given"))]
+    PeerClosed(Option<String>),
+
+    /// The peer stopped answering keepalives
+    #[error("peer stopped answering keepalives")]
+    PeerUnresponsive,
+
+    /// The peer ended the connection with an `Error` on the control context
+    #[error("connection failed by the peer ({kind}): {message}")]
+    PeerError { kind: ErrorType, message: String },
+
+    /// The connection ended because a side broke the protocol
+    #[error("protocol violation: {0}")]
+    ProtocolViolation(String),
+
+    /// The underlying stream of the connection failed
+    #[error("transport failure: {0}")]
+    Transport(Arc<UTPError>),
+
     /// The server rejected the handshake
     #[error("handshake rejected: {0}")]
     HandshakeReject(String),
 
+    /// The peer did not respond within the handshake deadline
+    #[error("handshake timed out waiting for {0}")]
+    HandshakeTimeout(String),
+
+    /// The peer speaks an incompatible protocol version
+    #[error("incompatible protocol version: local {local}, remote {remote}")]
+    IncompatibleVersion { local: Version, remote: Version },
+
+    /// The frame exceeds the largest frame the peer accepts
+    #[error("frame of {size} bytes exceeds the maximum of {max}")]
+    FrameTooLarge { size: u64, max: u64 },
+
+    /// A frame received from the peer could not be decoded
+    #[error("undecodable frame: {0}")]
+    Decode(DecodeError),
+
     /// Received malformed or invalid data
     #[error("malformed data: {0}")]
     MalformedData(String),
 
     /// Received an unexpected payload type
     #[error("malformed payload: {0} {1:?}")]
-    MalformedPayload(String, Payload),
+    MalformedPayload(String, Box<Payload>),
 }
@@ -0,0 +1,162 @@
+//! Well-known extensions and their negotiation rules.
+//!
+//! Each side advertises the extensions it supports in its hello. The server
+//! computes the agreed set with [`negotiate_extensions`] and returns it in the
+//! `ServerHello`, where it becomes available as
+//! [`Connection::extensions`](crate::Connection::extensions) on both sides.
+
+use std::time::Duration;
+
+use crate::schema::Extensions;
+
+/// Compression algorithms, comma-separated in order of preference.
+///
+/// The agreed value is the first client preference the server supports.
+pub const COMPRESSION: &str = "compression";
+
+/// Maximum frame size in bytes. The agreed value is the smaller one.
+pub const MAX_FRAME_SIZE: &str = "max_frame_size";
+
+/// Keepalive interval in milliseconds. The agreed value is the larger one.
+pub const KEEPALIVE_INTERVAL: &str = "keepalive_interval";
+
+/// Supported payload kinds, comma-separated.
+///
+/// The agreed value is the intersection, in client order.
+pub const PAYLOAD_KINDS: &str = "payload_kinds";
+
+impl Extensions {
+    pub fn with_compression(self, algorithms: &[&str]) -> Self {
+        self.with(COMPRESSION, algorithms.join(","))
+    }
+
+    pub fn compression(&self) -> Option<&str> {
+        self.get(COMPRESSION)
+    }
+
+    pub fn with_max_frame_size(self, size: u64) -> Self {
+        self.with(MAX_FRAME_SIZE, size.to_string())
+    }
+
+    pub fn max_frame_size(&self) -> Option<u64> {
+        self.get(MAX_FRAME_SIZE)?.parse().ok()
+    }
+
+    pub fn with_keepalive_interval(self, interval: Duration) -> Self {
+        self.with(KEEPALIVE_INTERVAL, interval.as_millis().to_string())
+    }
+
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.get(KEEPALIVE_INTERVAL)?
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+    }
+
+    pub fn with_payload_kinds(self, kinds: &[&str]) -> Self {
+        self.with(PAYLOAD_KINDS, kinds.join(","))
+    }
+
+    pub fn payload_kinds(&self) -> Vec<&str> {
+        self.get(PAYLOAD_KINDS)
+            .map(|kinds| split_list(kinds).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the extensions to send in a hello, advertising
+    /// `max_frame_size` unless it is set already.
+    pub(crate) fn advertised(&self, max_frame_size: u64) -> Self {
+        if self.contains(MAX_FRAME_SIZE) {
+            self.clone()
+        } else {
+            self.clone().with_max_frame_size(max_frame_size)
+        }
+    }
+}
+
+/// Computes the agreed set of extensions from both advertisements.
+///
+/// Only extensions advertised by both sides are agreed on. Well-known
+/// extensions are merged by their rule, any other extension only if both
+/// sides advertise the same value.
+pub fn negotiate_extensions(client: &Extensions, server: &Extensions) -> Extensions {
+    let mut agreed = Extensions::new();
+
+    for (name, client_value) in client.iter() {
+        let Some(server_value) = server.get(name) else {
+            continue;
+        };
+
+        let value = match name {
+            COMPRESSION => split_list(client_value)
+                .find(|c| split_list(server_value).any(|s| s == *c))
+                .map(str::to_string),
+            PAYLOAD_KINDS => {
+                let kinds = split_list(client_value)
+                    .filter(|c| split_list(server_value).any(|s| s == *c))
+                    .collect::<Vec<_>>();
+                (!kinds.is_empty()).then(|| kinds.join(","))
+            }
+            MAX_FRAME_SIZE => merge_numbers(client_value, server_value, u64::min),
+            KEEPALIVE_INTERVAL => merge_numbers(client_value, server_value, u64::max),
+            _ => (client_value == server_value).then(|| client_value.to_string()),
+        };
+
+        if let Some(value) = value {
+            agreed.insert(name, value);
+        }
+    }
+
+    agreed
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|e| !e.is_empty())
+}
+
+fn merge_numbers(a: &str, b: &str, merge: fn(u64, u64) -> u64) -> Option<String> {
+    let a = a.parse().ok()?;
+    let b = b.parse().ok()?;
+    Some(merge(a, b).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{core::common::extension::negotiate_extensions, schema::Extensions};
+
+    #[test]
+    fn test_negotiate_extensions() {
+        let client = Extensions::new()
+            .with_compression(&["zstd", "gzip"])
+            .with_max_frame_size(1 << 20)
+            .with_keepalive_interval(Duration::from_secs(5))
+            .with_payload_kinds(&["arbitrary", "benchmark"])
+            .with("x-custom", "a")
+            .with("x-client-only", "1");
+        let server = Extensions::new()
+            .with_compression(&["gzip"])
+            .with_max_frame_size(1 << 16)
+            .with_keepalive_interval(Duration::from_secs(10))
+            .with_payload_kinds(&["arbitrary"])
+            .with("x-custom", "b");
+
+        let agreed = negotiate_extensions(&client, &server);
+
+        assert_eq!(agreed.compression(), Some("gzip"));
+        assert_eq!(agreed.max_frame_size(), Some(1 << 16));
+        assert_eq!(agreed.keepalive_interval(), Some(Duration::from_secs(10)));
+        assert_eq!(agreed.payload_kinds(), vec!["arbitrary"]);
+        assert!(!agreed.contains("x-custom"));
+        assert!(!agreed.contains("x-client-only"));
+    }
+
+    #[test]
+    fn test_negotiate_no_common_compression() {
+        let client = Extensions::new().with_compression(&["zstd"]);
+        let server = Extensions::new().with_compression(&["gzip"]);
+
+        assert!(negotiate_extensions(&client, &server).is_empty());
+    }
+}
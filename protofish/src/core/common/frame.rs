@@ -0,0 +1,35 @@
+use crate::{constant::DEFAULT_MAX_FRAME_SIZE, core::common::decode::DecodePolicy};
+
+/// How frames are sent to and received from the peer.
+#[derive(Debug, Clone)]
+pub struct FrameOptions {
+    /// Largest frame sent or received, in bytes.
+    ///
+    /// Advertised as the `max_frame_size` extension unless the extensions of
+    /// the hello already contain it. A smaller agreed value takes precedence.
+    pub max_frame_size: u64,
+
+    /// What happens to frames received from the peer that cannot be decoded.
+    pub decode_policy: DecodePolicy,
+}
+
+impl Default for FrameOptions {
+    fn default() -> Self {
+        Self {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            decode_policy: DecodePolicy::default(),
+        }
+    }
+}
+
+impl FrameOptions {
+    pub fn with_max_frame_size(mut self, max_frame_size: u64) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn with_decode_policy(mut self, decode_policy: DecodePolicy) -> Self {
+        self.decode_policy = decode_policy;
+        self
+    }
+}
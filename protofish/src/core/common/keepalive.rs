@@ -0,0 +1,220 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::{
+    constant::{CLIENT_KEEPALIVE_CONTEXT_ID, SERVER_KEEPALIVE_CONTEXT_ID},
+    core::common::pmc::PMC,
+    internal::pmc_frame::{PMCFrame, Received, Subscription, Termination},
+    schema::{ContextId, Message, Payload},
+    utp::UTPStream,
+};
+
+/// Heartbeat settings of a connection.
+#[derive(Debug, Clone)]
+pub struct KeepaliveOptions {
+    /// Time between two keepalives.
+    ///
+    /// The interval agreed on via the `keepalive_interval` extension takes
+    /// precedence if present.
+    pub interval: Duration,
+
+    /// Number of consecutive unanswered keepalives after which the peer is
+    /// considered dead.
+    pub max_missed: u32,
+}
+
+impl Default for KeepaliveOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            max_missed: 3,
+        }
+    }
+}
+
+impl KeepaliveOptions {
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_missed(mut self, max_missed: u32) -> Self {
+        self.max_missed = max_missed;
+        self
+    }
+}
+
+/// Handle to the heartbeat task of a connection.
+///
+/// The task sends a `Keepalive` on the reserved context of its side every
+/// interval, and the peer echoes it back. Once `max_missed` keepalives in a row
+/// go unanswered, the PMC is shut down, failing all open contexts.
+pub(crate) struct Heartbeat {
+    rtt: Arc<Mutex<Option<Duration>>>,
+}
+
+impl Heartbeat {
+    pub(crate) fn spawn<S: UTPStream>(pmc: &PMC<S>, interval: Duration, max_missed: u32) -> Self {
+        let context_id = if pmc.is_server() {
+            SERVER_KEEPALIVE_CONTEXT_ID
+        } else {
+            CLIENT_KEEPALIVE_CONTEXT_ID
+        };
+
+        let rtt = Arc::new(Mutex::new(None));
+        let echoes = pmc.frame().subscribe_context(context_id);
+
+        tokio::spawn(run_heartbeat(
+            Arc::downgrade(pmc.frame()),
+            context_id,
+            echoes,
+            interval,
+            max_missed,
+            rtt.clone(),
+        ));
+
+        Self { rtt }
+    }
+
+    /// Returns the round-trip time of the latest answered keepalive.
+    pub(crate) fn rtt(&self) -> Option<Duration> {
+        *self.rtt.lock()
+    }
+}
+
+async fn run_heartbeat<S: UTPStream>(
+    frame: Weak<PMCFrame<S>>,
+    context_id: ContextId,
+    mut echoes: Subscription,
+    interval: Duration,
+    max_missed: u32,
+    rtt: Arc<Mutex<Option<Duration>>>,
+) {
+    let mut missed = 0;
+
+    loop {
+        // late echoes of missed keepalives would skew the round-trip time
+        while echoes.try_recv().is_some() {}
+
+        let Some(pmc_frame) = frame.upgrade() else {
+            break;
+        };
+
+        // queued rather than sent, so a blocked write counts as a miss instead
+        // of parking the heartbeat
+        let sent_at = Instant::now();
+        pmc_frame.queue_frame(Message {
+            context_id,
+            payload: Payload::Keepalive,
+        });
+        drop(pmc_frame);
+
+        match tokio::time::timeout(interval, echoes.recv()).await {
+            // dropped echoes prove the peer alive as well
+            Ok(Received::Payload(_) | Received::Overflowed(_) | Received::Malformed(_)) => {
+                missed = 0;
+                *rtt.lock() = Some(sent_at.elapsed());
+
+                tokio::time::sleep_until(sent_at + interval).await;
+            }
+            Ok(Received::Ended | Received::Closed(_)) => break,
+            // the echo may wait behind a full queue
+            Err(_) if frame.upgrade().is_some_and(|frame| frame.is_stalled()) => {}
+            Err(_) => {
+                missed += 1;
+
+                if missed >= max_missed {
+                    tracing::warn!("Peer missed {} keepalives, closing connection", missed);
+
+                    if let Some(pmc_frame) = frame.upgrade() {
+                        pmc_frame.terminate(Termination::Unresponsive);
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        core::common::{keepalive::Heartbeat, pmc::PMC},
+        schema::{ArbitaryData, Payload},
+        utp::tests::stream::mock_utp_stream_pairs,
+    };
+
+    #[tokio::test]
+    async fn test_heartbeat_rtt() {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        let client = PMC::new(false, a);
+        let _server = PMC::new(true, b);
+
+        let heartbeat = Heartbeat::spawn(&client, Duration::from_millis(20), 3);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(heartbeat.rtt().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_dead_peer() {
+        // the peer stream is never read, so keepalives are not echoed
+        let (a, _b) = mock_utp_stream_pairs(0);
+
+        let client = PMC::new(false, a);
+        let (_tx, rx) = client.create_context();
+
+        let heartbeat = Heartbeat::spawn(&client, Duration::from_millis(10), 2);
+
+        assert!(rx.read().await.is_err());
+        assert!(client.next_context().await.is_err());
+        assert!(heartbeat.rtt().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_blocked_write() {
+        // the peer stream is never read, so a large write blocks for good
+        let (a, _b) = mock_utp_stream_pairs(0);
+
+        let client = PMC::new(false, a);
+        let (tx, rx) = client.create_context();
+        tokio::spawn(async move {
+            let _ = tx
+                .write(Payload::ArbitaryData(ArbitaryData {
+                    content: vec![0; 4096],
+                }))
+                .await;
+        });
+
+        let _heartbeat = Heartbeat::spawn(&client, Duration::from_millis(10), 2);
+
+        let read = tokio::time::timeout(Duration::from_secs(1), rx.read()).await;
+        assert!(read.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_not_surfaced_as_context() {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        let client = PMC::new(false, a);
+        let server = PMC::new(true, b);
+
+        let _heartbeat = Heartbeat::spawn(&client, Duration::from_millis(5), 3);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let (tx, _rx) = client.create_context();
+        tx.write(Payload::Ok).await.unwrap();
+
+        let (_, rx) = server.next_context().await.unwrap();
+        assert!(matches!(rx.read().await.unwrap(), Payload::Ok));
+    }
+}
@@ -1,7 +1,20 @@
 pub mod arbitrary;
+pub mod benchmark;
+pub mod channel;
+pub mod codec;
 pub mod connection;
 pub mod context;
 pub mod counter;
+pub mod decode;
 pub mod error;
+pub mod extension;
+pub mod frame;
+pub mod keepalive;
 pub mod pmc;
+pub mod queue;
+#[cfg(feature = "rpc")]
+pub mod rpc;
 pub mod stream;
+pub(crate) mod timeout;
+pub mod typed;
+pub mod version;
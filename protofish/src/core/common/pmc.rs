@@ -6,14 +6,17 @@ use crate::{
     core::common::{
         context::{Context, ContextReader, ContextWriter},
         counter::ContextCounter,
+        decode::DecodePolicy,
+        error::ConnectionError,
+        queue::QueueOptions,
     },
-    internal::pmc_frame::PMCFrame,
-    schema::Payload,
+    internal::pmc_frame::{IncomingContext, PMCFrame, Subscription},
+    schema::{ContextId, Payload},
     utp::UTPStream,
 };
 
 pub struct PMC<U: UTPStream> {
-    counter: Mutex<ContextCounter>,
+    counter: Arc<Mutex<ContextCounter>>,
     frame: Arc<PMCFrame<U>>,
 }
 
@@ -21,47 +24,123 @@ impl<S> PMC<S>
 where
     S: UTPStream,
 {
+    #[cfg(test)]
     pub(crate) fn new(is_server: bool, utp_stream: S) -> Self {
+        Self::with_limits(
+            is_server,
+            utp_stream,
+            QueueOptions::default(),
+            crate::constant::DEFAULT_MAX_FRAME_SIZE,
+            DecodePolicy::default(),
+        )
+    }
+
+    pub(crate) fn with_limits(
+        is_server: bool,
+        utp_stream: S,
+        queue: QueueOptions,
+        max_frame_size: u64,
+        decode_policy: DecodePolicy,
+    ) -> Self {
+        let frame = PMCFrame::new(is_server, utp_stream, queue, max_frame_size, decode_policy);
+
         Self {
-            counter: ContextCounter::new(is_server).into(),
-            frame: PMCFrame::new(utp_stream).into(),
+            counter: Arc::new(ContextCounter::new(is_server).into()),
+            frame: frame.into(),
+        }
+    }
+
+    /// Returns the context ID counter shared with this PMC.
+    pub(crate) fn counter(&self) -> Arc<Mutex<ContextCounter>> {
+        self.counter.clone()
+    }
+
+    /// Continues a resumed session: context IDs continue from `counter` if
+    /// there is one, and the peer's from where it stopped.
+    pub(crate) fn resume(&mut self, counter: Option<Arc<Mutex<ContextCounter>>>) {
+        if let Some(counter) = counter {
+            self.counter = counter;
         }
+        self.frame.peer_resumed();
     }
 
     pub fn create_context(&self) -> Context<S> {
         let context_id = self.counter.lock().next_context_id();
-        self.make_context(context_id, None)
+        self.make_context(context_id)
     }
 
-    fn make_context(&self, context_id: u64, initial_payload: Option<Payload>) -> Context<S> {
-        let writer = ContextWriter {
-            context_id,
-            pmc_frame: self.frame.clone(),
-        };
+    /// Returns whether this PMC belongs to the server side of the connection.
+    pub(crate) fn is_server(&self) -> bool {
+        self.counter.lock().is_server
+    }
 
-        let receiver = self.frame.subscribe_context(context_id, initial_payload);
+    pub(crate) fn frame(&self) -> &Arc<PMCFrame<S>> {
+        &self.frame
+    }
 
-        let reader = ContextReader {
-            receiver: receiver.into(),
-        };
+    fn make_context(&self, context_id: ContextId) -> Context<S> {
+        let receiver = self.frame.subscribe_context(context_id);
+        let writer = ContextWriter::new(context_id, self.frame.clone(), &receiver);
 
-        (writer, reader)
+        (writer, ContextReader::new(None, receiver))
     }
 
-    pub async fn next_context(&self) -> Option<Context<S>> {
-        let msg = self.frame.next_context_message().await?;
+    /// Waits for the next context opened by the peer without handing it out.
+    pub(crate) async fn next_incoming(&self) -> Result<IncomingContext, ConnectionError> {
+        self.frame.next_context().await
+    }
+
+    /// Builds a context opened by the peer.
+    ///
+    /// `first` is returned by the first read, usually the payload the context
+    /// was opened with.
+    pub(crate) fn open_incoming(
+        &self,
+        context_id: ContextId,
+        first: Option<Payload>,
+        receiver: Subscription,
+    ) -> Context<S> {
+        let writer = ContextWriter::new(context_id, self.frame.clone(), &receiver);
+
+        (writer, ContextReader::new(first, receiver))
+    }
+
+    /// Waits for the next context opened by the peer.
+    ///
+    /// Fails once the PMC stopped receiving, with the decode failure that
+    /// ended it if any.
+    pub async fn next_context(&self) -> Result<Context<S>, ConnectionError> {
+        let incoming = self.next_incoming().await?;
+        let message = incoming.message;
 
-        let ctx = self.make_context(msg.context_id, Some(msg.payload));
+        let ctx = self.open_incoming(message.context_id, Some(message.payload), incoming.receiver);
 
-        Some(ctx)
+        Ok(ctx)
     }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
     use crate::{
-        core::common::pmc::PMC, schema::Payload, utp::tests::stream::mock_utp_stream_pairs,
+        constant::{CONTROL_CONTEXT_ID, DEFAULT_MAX_FRAME_SIZE, SERVER_KEEPALIVE_CONTEXT_ID},
+        core::common::{
+            context::Context,
+            decode::DecodePolicy,
+            error::ConnectionError,
+            pmc::PMC,
+            queue::{OverflowPolicy, QueueOptions},
+        },
+        internal::serialize::{deserialize_message, serialize_message},
+        schema::{ArbitaryData, ErrorType, Message, Payload},
+        utp::{
+            UTPStream,
+            tests::stream::{MockUTPStream, mock_utp_stream_pairs},
+        },
     };
 
     #[tokio::test]
@@ -83,4 +162,548 @@ mod tests {
         let ba = b_rx.read().await.unwrap();
         assert!(matches!(ba, Payload::Keepalive));
     }
+
+    #[tokio::test]
+    async fn test_pmc_context_end() {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        let pmc_a = PMC::new(true, a);
+        let pmc_b = PMC::new(false, b);
+
+        for _ in 0..10 {
+            let (b_tx, b_rx) = pmc_b.create_context();
+            b_tx.write(Payload::Ok).await.unwrap();
+            b_tx.close().await.unwrap();
+            assert!(b_tx.write(Payload::Ok).await.is_err());
+
+            let (a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+            assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+            assert!(matches!(
+                a_rx.read().await,
+                Err(ConnectionError::ContextEnded)
+            ));
+
+            // dropping the writer ends the context as well
+            drop(a_tx);
+            assert!(matches!(
+                b_rx.read().await,
+                Err(ConnectionError::ContextEnded)
+            ));
+        }
+
+        assert_eq!(pmc_a.frame().subscribed(), 0);
+
+        // late messages of an ended context do not open a new one
+        let (b_tx, b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        let (_, a_rx) = pmc_a.next_context().await.unwrap();
+        drop(a_rx);
+        b_tx.write(Payload::Keepalive).await.unwrap();
+        drop((b_tx, b_rx));
+
+        let (b_tx, _b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        let (a_tx, _) = pmc_a.next_context().await.unwrap();
+        assert_eq!(a_tx.context_id, b_tx.context_id);
+        assert_eq!(pmc_b.frame().subscribed(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pmc_context_dropped_outside_runtime() {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        let pmc_a = PMC::new(true, a);
+        let pmc_b = PMC::new(false, b);
+
+        let (b_tx, b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        let (_a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+
+        std::thread::spawn(move || drop((b_tx, b_rx)))
+            .join()
+            .unwrap();
+        assert!(matches!(
+            a_rx.read().await,
+            Err(ConnectionError::ContextEnded)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_context_used_out_of_order() {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        let pmc_a = PMC::new(true, a);
+        let pmc_b = PMC::new(false, b);
+
+        // a context created first may be used last
+        let (first_tx, _first_rx) = pmc_b.create_context();
+        let (second_tx, _second_rx) = pmc_b.create_context();
+
+        second_tx.write(Payload::Ok).await.unwrap();
+        let (a_tx, _) = pmc_a.next_context().await.unwrap();
+        assert_eq!(a_tx.context_id, second_tx.context_id);
+
+        first_tx.write(Payload::Ok).await.unwrap();
+        let (a_tx, _) = pmc_a.next_context().await.unwrap();
+        assert_eq!(a_tx.context_id, first_tx.context_id);
+    }
+
+    fn bounded_pair(queue: QueueOptions) -> (PMC<MockUTPStream>, PMC<MockUTPStream>) {
+        let (a, b) = mock_utp_stream_pairs(0);
+
+        (
+            PMC::with_limits(
+                true,
+                a,
+                queue,
+                DEFAULT_MAX_FRAME_SIZE,
+                DecodePolicy::default(),
+            ),
+            PMC::new(false, b),
+        )
+    }
+
+    /// Opens a context on `pmc_b` and writes `count` keepalives after its
+    /// first payload, returning once `pmc_a` routed all of them.
+    async fn flood(
+        pmc_a: &PMC<MockUTPStream>,
+        pmc_b: &PMC<MockUTPStream>,
+        count: usize,
+    ) -> (Context<MockUTPStream>, Context<MockUTPStream>) {
+        let (b_tx, b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        for _ in 0..count {
+            b_tx.write(Payload::Keepalive).await.unwrap();
+        }
+        let a_ctx = pmc_a.next_context().await.unwrap();
+
+        // contexts are routed in order, so this one arrives after the flood
+        let (sync_tx, _sync_rx) = pmc_b.create_context();
+        sync_tx.write(Payload::Ok).await.unwrap();
+        pmc_a.next_context().await.unwrap();
+
+        (a_ctx, (b_tx, b_rx))
+    }
+
+    fn is_resource_exhausted(payload: Payload) -> bool {
+        matches!(
+            payload,
+            Payload::Error(error) if error.error_type == ErrorType::ResourceExhausted
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pmc_overflow_reject() {
+        let queue = QueueOptions::default()
+            .with_capacity(2)
+            .with_overflow(OverflowPolicy::Reject);
+        let (pmc_a, pmc_b) = bounded_pair(queue);
+
+        let ((_a_tx, a_rx), (b_tx, b_rx)) = flood(&pmc_a, &pmc_b, 4).await;
+
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        assert!(matches!(
+            a_rx.read().await,
+            Err(ConnectionError::ContextOverflow(2))
+        ));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(is_resource_exhausted(b_rx.read().await.unwrap()));
+
+        // the context keeps working
+        b_tx.write(Payload::Ok).await.unwrap();
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_overflow_close() {
+        let queue = QueueOptions::default()
+            .with_capacity(2)
+            .with_overflow(OverflowPolicy::Close);
+        let (pmc_a, pmc_b) = bounded_pair(queue);
+
+        let ((a_tx, a_rx), (b_tx, b_rx)) = flood(&pmc_a, &pmc_b, 4).await;
+
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(matches!(
+            a_rx.read().await,
+            Err(ConnectionError::ContextOverflow(_))
+        ));
+        assert!(is_resource_exhausted(b_rx.read().await.unwrap()));
+
+        // the context ended on this side, without a `Close` of its own
+        assert!(matches!(
+            a_tx.write(Payload::Ok).await,
+            Err(ConnectionError::ClosedStream)
+        ));
+        drop((a_tx, a_rx));
+        let ended = tokio::time::timeout(Duration::from_millis(50), b_rx.read()).await;
+        assert!(ended.is_err());
+
+        // nor do later payloads open it again
+        b_tx.write(Payload::Ok).await.unwrap();
+        let reopened = tokio::time::timeout(Duration::from_millis(50), pmc_a.next_context()).await;
+        assert!(reopened.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pmc_overflow_bytes() {
+        let queue = QueueOptions::default()
+            .with_max_bytes(64)
+            .with_overflow(OverflowPolicy::Reject);
+        let (pmc_a, pmc_b) = bounded_pair(queue);
+
+        let (b_tx, b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        let (_a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+
+        // the first payload fits even though it is larger than the limit
+        let data = || {
+            Payload::ArbitaryData(ArbitaryData {
+                content: vec![0; 100],
+            })
+        };
+        b_tx.write(data()).await.unwrap();
+        b_tx.write(data()).await.unwrap();
+
+        assert!(is_resource_exhausted(b_rx.read().await.unwrap()));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        assert!(matches!(
+            a_rx.read().await,
+            Err(ConnectionError::ContextOverflow(1))
+        ));
+        assert!(matches!(
+            a_rx.read().await.unwrap(),
+            Payload::ArbitaryData(_)
+        ));
+
+        // reading frees the bytes again
+        b_tx.write(data()).await.unwrap();
+        assert!(matches!(
+            a_rx.read().await.unwrap(),
+            Payload::ArbitaryData(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_overflow_block() {
+        let (pmc_a, pmc_b) = bounded_pair(QueueOptions::default().with_capacity(1));
+
+        let (b_tx, _b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        b_tx.write(Payload::Keepalive).await.unwrap();
+        b_tx.write(Payload::Keepalive).await.unwrap();
+        let (_a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+
+        let (other_tx, _other_rx) = pmc_b.create_context();
+        other_tx.write(Payload::Ok).await.unwrap();
+
+        // the reader waits for the full context before routing anything else
+        let blocked = tokio::time::timeout(Duration::from_millis(50), pmc_a.next_context()).await;
+        assert!(blocked.is_err());
+
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Keepalive));
+        assert!(pmc_a.next_context().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pmc_overflow_block_reserved() {
+        let (pmc_a, pmc_b) = bounded_pair(QueueOptions::default().with_capacity(1));
+        let _echoes = pmc_a.frame().subscribe_context(SERVER_KEEPALIVE_CONTEXT_ID);
+
+        // echoes nobody reads do not stall the reader
+        for _ in 0..3 {
+            pmc_b
+                .frame()
+                .send_frame(Message {
+                    context_id: SERVER_KEEPALIVE_CONTEXT_ID,
+                    payload: Payload::Keepalive,
+                })
+                .await
+                .unwrap();
+        }
+
+        let (b_tx, _b_rx) = pmc_b.create_context();
+        b_tx.write(Payload::Ok).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_millis(50), pmc_a.next_context()).await;
+        assert!(next.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pmc_backlog() {
+        let queue = QueueOptions::default()
+            .with_backlog(1)
+            .with_overflow(OverflowPolicy::Reject);
+        let (pmc_a, pmc_b) = bounded_pair(queue);
+
+        let (first_tx, _first_rx) = pmc_b.create_context();
+        first_tx.write(Payload::Ok).await.unwrap();
+        let (refused_tx, refused_rx) = pmc_b.create_context();
+        refused_tx.write(Payload::Ok).await.unwrap();
+
+        assert!(is_resource_exhausted(refused_rx.read().await.unwrap()));
+        assert!(matches!(
+            refused_rx.read().await,
+            Err(ConnectionError::ContextEnded)
+        ));
+
+        let (a_tx, _) = pmc_a.next_context().await.unwrap();
+        assert_eq!(a_tx.context_id, first_tx.context_id);
+    }
+
+    #[tokio::test]
+    async fn test_pmc_oversized_frame() {
+        let (a, b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::with_limits(
+            true,
+            a,
+            QueueOptions::default(),
+            1024,
+            DecodePolicy::default(),
+        );
+        let (mut b_writer, mut b_reader) = b.split();
+
+        b_writer.write_u64_le(u64::MAX).await.unwrap();
+
+        // the peer is told why before the stream ends
+        let len = b_reader.read_u64_le().await.unwrap();
+        let mut buf = vec![0; len as usize];
+        b_reader.read_exact(&mut buf).await.unwrap();
+        let message = deserialize_message(&buf).unwrap();
+        assert_eq!(message.context_id, CONTROL_CONTEXT_ID);
+        assert!(is_resource_exhausted(message.payload));
+        assert!(b_reader.read_u8().await.is_err());
+
+        pmc_a.frame().closed().await;
+        assert!(matches!(
+            pmc_a.next_context().await,
+            Err(ConnectionError::ProtocolViolation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_frame_too_large() {
+        let (a, _b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::with_limits(
+            true,
+            a,
+            QueueOptions::default(),
+            64,
+            DecodePolicy::default(),
+        );
+
+        let (a_tx, _a_rx) = pmc_a.create_context();
+        let result = a_tx
+            .write(Payload::ArbitaryData(ArbitaryData {
+                content: vec![0; 100],
+            }))
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ConnectionError::FrameTooLarge { max: 64, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_malformed_payloads() {
+        use prost::Message as _;
+
+        use crate::prost_generated::payload::v1;
+
+        let (a, b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::new(true, a);
+        let (mut b_writer, mut b_reader) = b.split();
+
+        let mut send_raw = async |buf: &[u8]| {
+            b_writer.write_u64_le(buf.len() as u64).await.unwrap();
+            b_writer.write_all(buf).await.unwrap();
+        };
+
+        // a payload type of a newer version
+        let unknown = v1::Message {
+            context_id: 0,
+            payload: Some(v1::Payload { payload: None }),
+        };
+        send_raw(&unknown.encode_to_vec()).await;
+
+        // a StreamOpen without its meta, opening a context
+        let malformed = |context_id| {
+            v1::Message {
+                context_id,
+                payload: Some(v1::Payload {
+                    payload: Some(v1::payload::Payload::StreamOpen(v1::StreamOpen {
+                        stream_id: 1,
+                        meta: None,
+                    })),
+                }),
+            }
+            .encode_to_vec()
+        };
+        send_raw(&malformed(2)).await;
+        send_raw(&malformed(2)).await;
+
+        // not protobuf at all
+        send_raw(&[0xff, 0xff]).await;
+
+        let valid = |context_id| {
+            serialize_message(crate::schema::Message {
+                context_id,
+                payload: Payload::Ok,
+            })
+        };
+        send_raw(&valid(4)).await;
+
+        let (a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+        assert_eq!(a_tx.context_id, 4);
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        // the unknown payload and the frame without a context
+        assert_eq!(pmc_a.frame().skipped_payloads(), 2);
+
+        // a malformed payload fails an open context
+        send_raw(&malformed(4)).await;
+        send_raw(&malformed(4)).await;
+        send_raw(&valid(4)).await;
+        assert!(matches!(a_rx.read().await, Err(ConnectionError::Decode(_))));
+        assert!(matches!(
+            a_rx.read().await,
+            Err(ConnectionError::ContextEnded)
+        ));
+        assert!(matches!(
+            a_tx.write(Payload::Ok).await,
+            Err(ConnectionError::ClosedStream)
+        ));
+
+        // each failed context is reported once
+        let mut read_report = async || {
+            let len = b_reader.read_u64_le().await.unwrap();
+            let mut buf = vec![0; len as usize];
+            b_reader.read_exact(&mut buf).await.unwrap();
+            deserialize_message(&buf).unwrap()
+        };
+        for context_id in [2, 4] {
+            let report = read_report().await;
+            assert_eq!(report.context_id, context_id);
+            assert!(matches!(
+                report.payload,
+                Payload::Error(error) if error.error_type == ErrorType::Malformed
+            ));
+        }
+        let more = tokio::time::timeout(Duration::from_millis(50), read_report()).await;
+        assert!(more.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pmc_fatal_decode() {
+        let (a, b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::with_limits(
+            true,
+            a,
+            QueueOptions::default(),
+            DEFAULT_MAX_FRAME_SIZE,
+            DecodePolicy::Fatal,
+        );
+        let (mut b_writer, mut b_reader) = b.split();
+
+        let mut send_raw = async |buf: &[u8]| {
+            b_writer.write_u64_le(buf.len() as u64).await.unwrap();
+            b_writer.write_all(buf).await.unwrap();
+        };
+
+        let valid = serialize_message(crate::schema::Message {
+            context_id: 2,
+            payload: Payload::Ok,
+        });
+        send_raw(&valid).await;
+
+        let (_a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        let pending = tokio::spawn(async move { a_rx.read().await });
+
+        send_raw(&[0xff, 0xff]).await;
+
+        // the failure ends both the pending read and the accept loop
+        assert!(matches!(
+            pending.await.unwrap(),
+            Err(ConnectionError::Decode(_))
+        ));
+        assert!(matches!(
+            pmc_a.next_context().await,
+            Err(ConnectionError::Decode(_))
+        ));
+
+        let len = b_reader.read_u64_le().await.unwrap();
+        let mut buf = vec![0; len as usize];
+        b_reader.read_exact(&mut buf).await.unwrap();
+        let report = deserialize_message(&buf).unwrap();
+        assert_eq!(report.context_id, CONTROL_CONTEXT_ID);
+        assert!(matches!(
+            report.payload,
+            Payload::Error(error) if error.error_type == ErrorType::Malformed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_truncated_frame() {
+        let (a, b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::new(true, a);
+        let (mut b_writer, _b_reader) = b.split();
+
+        // the stream ends in the middle of a frame
+        b_writer.write_u64_le(100).await.unwrap();
+        b_writer.write_all(&[0; 10]).await.unwrap();
+        b_writer.shutdown().await.unwrap();
+
+        assert!(matches!(
+            pmc_a.next_context().await,
+            Err(ConnectionError::Transport(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pmc_termination_cause() {
+        let (a, b) = mock_utp_stream_pairs(0);
+        let pmc_a = PMC::new(true, a);
+        let (mut b_writer, _b_reader) = b.split();
+
+        let frame = |context_id, payload| {
+            let buf = serialize_message(crate::schema::Message {
+                context_id,
+                payload,
+            });
+            [&(buf.len() as u64).to_le_bytes()[..], &buf].concat()
+        };
+
+        b_writer.write_all(&frame(2, Payload::Ok)).await.unwrap();
+        let (a_tx, a_rx) = pmc_a.next_context().await.unwrap();
+        assert!(matches!(a_rx.read().await.unwrap(), Payload::Ok));
+        let pending = tokio::spawn(async move { a_rx.read().await });
+
+        // the peer gives up on the connection, then ends the stream
+        let error = Payload::Error(crate::schema::Error {
+            error_type: ErrorType::Internal,
+            message: "nope".into(),
+        });
+        b_writer
+            .write_all(&frame(CONTROL_CONTEXT_ID, error))
+            .await
+            .unwrap();
+        b_writer.shutdown().await.unwrap();
+
+        let is_peer_error = |result: Result<_, ConnectionError>| {
+            matches!(
+                result,
+                Err(ConnectionError::PeerError { kind: ErrorType::Internal, message })
+                    if message == "nope"
+            )
+        };
+        assert!(is_peer_error(pending.await.unwrap().map(drop)));
+        assert!(is_peer_error(pmc_a.next_context().await.map(drop)));
+        assert!(is_peer_error(a_tx.write(Payload::Ok).await));
+    }
 }
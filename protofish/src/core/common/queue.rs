@@ -0,0 +1,86 @@
+/// What happens to a payload arriving at a context whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Waits for the reader to catch up.
+    ///
+    /// Payloads of all other contexts wait as well (head-of-line blocking).
+    /// Meanwhile, missed keepalive echoes are not held against the peer.
+    /// Reserved contexts never wait; their payloads are dropped instead.
+    #[default]
+    Block,
+
+    /// Drops the payload.
+    ///
+    /// The next read of the context fails with
+    /// `ConnectionError::ContextOverflow`, and the peer receives an `Error`
+    /// of type `ResourceExhausted` on the context.
+    Reject,
+
+    /// Ends the context.
+    ///
+    /// The reader fails with `ConnectionError::ContextOverflow` after the
+    /// queued payloads, later writes fail with `ConnectionError::ClosedStream`,
+    /// and the peer receives an `Error` of type `ResourceExhausted`.
+    Close,
+}
+
+/// Limits of the queues between the PMC reader and the contexts.
+#[derive(Debug, Clone)]
+pub struct QueueOptions {
+    /// Number of payloads queued per context before it overflows.
+    pub capacity: usize,
+
+    /// Number of bytes queued per context before it overflows.
+    ///
+    /// A payload larger than this fits into an empty queue.
+    pub max_bytes: usize,
+
+    /// Number of contexts opened by the peer that wait to be handed out.
+    ///
+    /// On overflow, new contexts are refused unless the policy is
+    /// [`OverflowPolicy::Block`].
+    pub backlog: usize,
+
+    /// What happens once a queue is full.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            max_bytes: 16 * 1024 * 1024,
+            backlog: 128,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+impl QueueOptions {
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_backlog(mut self, backlog: usize) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Returns `max_bytes` as a number of semaphore permits.
+    pub(crate) fn byte_limit(&self) -> u32 {
+        let max = tokio::sync::Semaphore::MAX_PERMITS.min(u32::MAX as usize);
+
+        self.max_bytes.clamp(1, max) as u32
+    }
+}
@@ -0,0 +1,404 @@
+//! Runtime of services generated by `protofish-build`.
+//!
+//! Every call runs on its own context. The client opens it with the path of
+//! the method, e.g. `/helloworld.Greeter/SayHello`, followed by the request
+//! messages, and ends it once it sent all of them. The server answers with
+//! the response messages and ends the context as well, or fails it with an
+//! `Error` payload carrying the [`Status`].
+//!
+//! Messages are encoded with [`ProstCodec`], one `ArbitaryData` payload each.
+
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use crate::{
+    core::common::{
+        arbitrary::ArbError,
+        codec::{Codec, ProstCodec},
+        connection::Connection,
+        context::{ContextReader, ContextWriter},
+        error::ConnectionError,
+    },
+    schema::{ArbitaryData, Error as ErrorPayload, ErrorType, Payload},
+    utp::{UTP, UTPStream},
+};
+
+pub use async_trait::async_trait;
+
+/// Outcome of a call that failed on the server.
+#[derive(Debug, Clone, Error)]
+#[error("{kind}: {message}")]
+pub struct Status {
+    pub kind: ErrorType,
+    pub message: String,
+}
+
+impl Status {
+    pub fn new(kind: ErrorType, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorType::NotFound, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorType::Internal, message)
+    }
+}
+
+impl From<ArbError> for Status {
+    fn from(error: ArbError) -> Self {
+        match error {
+            ArbError::Remote { kind, message } => Self::new(kind, message),
+            ArbError::Codec(e) => Self::new(ErrorType::Malformed, e.to_string()),
+            e => Self::internal(e.to_string()),
+        }
+    }
+}
+
+/// Writing half of a call, independent of the UTP.
+#[async_trait]
+trait Outbound: Send + Sync {
+    async fn write(&self, payload: Payload) -> Result<(), ConnectionError>;
+
+    async fn close(&self) -> Result<(), ConnectionError>;
+}
+
+#[async_trait]
+impl<S: UTPStream> Outbound for ContextWriter<S> {
+    async fn write(&self, payload: Payload) -> Result<(), ConnectionError> {
+        ContextWriter::write(self, payload).await
+    }
+
+    async fn close(&self) -> Result<(), ConnectionError> {
+        ContextWriter::close(self).await
+    }
+}
+
+/// Receives the messages of a call.
+pub struct Streaming<T> {
+    reader: ContextReader,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: prost::Message + Default> Streaming<T> {
+    fn new(reader: ContextReader) -> Self {
+        Self {
+            reader,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the next message, or `None` once the peer sent all of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArbError::Remote` if the peer failed the call,
+    /// `ArbError::Codec` if a message cannot be decoded, or
+    /// `ArbError::Connection` if the read fails.
+    pub async fn message(&mut self) -> Result<Option<T>, ArbError> {
+        loop {
+            match self.reader.read().await {
+                Ok(Payload::ArbitaryData(data)) => {
+                    let message = ProstCodec::decode(Bytes::from(data.content))?;
+                    return Ok(Some(message));
+                }
+                Ok(Payload::StreamClose(_)) => continue,
+                Ok(payload) => return Err(ArbError::unexpected(payload, "ArbitaryData")),
+                Err(ConnectionError::ContextEnded) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Returns the only message of a unary request or response.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`Streaming::message`], or with `ArbError::UnexpectedData`
+    /// if the peer ended the call without a message.
+    pub async fn single(&mut self) -> Result<T, ArbError> {
+        self.message()
+            .await?
+            .ok_or_else(|| ArbError::UnexpectedData("expected a message".into()))
+    }
+}
+
+/// Sends the messages of a call.
+///
+/// Clones send on the same call. The peer sees the end of the messages once
+/// [`MessageSink::close`] is called or all clones are dropped.
+pub struct MessageSink<T> {
+    writer: Arc<dyn Outbound>,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for MessageSink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: prost::Message + Default> MessageSink<T> {
+    fn new(writer: Arc<dyn Outbound>) -> Self {
+        Self {
+            writer,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encodes and sends a message.
+    pub async fn send(&self, message: &T) -> Result<(), ArbError> {
+        let content = ProstCodec::encode(message)?;
+        let payload = Payload::ArbitaryData(ArbitaryData {
+            content: content.into(),
+        });
+
+        self.writer.write(payload).await?;
+
+        Ok(())
+    }
+
+    /// Tells the peer that no more messages follow.
+    pub async fn close(&self) -> Result<(), ArbError> {
+        self.writer.close().await?;
+
+        Ok(())
+    }
+}
+
+/// A client-streaming call in progress.
+pub struct ClientStreaming<Req, Resp> {
+    requests: MessageSink<Req>,
+    response: Streaming<Resp>,
+}
+
+impl<Req, Resp> ClientStreaming<Req, Resp>
+where
+    Req: prost::Message + Default,
+    Resp: prost::Message + Default,
+{
+    pub async fn send(&self, request: &Req) -> Result<(), ArbError> {
+        self.requests.send(request).await
+    }
+
+    /// Ends the requests and waits for the response.
+    pub async fn finish(mut self) -> Result<Resp, ArbError> {
+        self.requests.close().await?;
+
+        self.response.single().await
+    }
+}
+
+/// Opens calls on a connection.
+pub struct Client<U: UTP> {
+    conn: Arc<Connection<U>>,
+}
+
+impl<U: UTP> Clone for Client<U> {
+    fn clone(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+        }
+    }
+}
+
+impl<U: UTP> Client<U> {
+    pub fn new(conn: Arc<Connection<U>>) -> Self {
+        Self { conn }
+    }
+
+    /// Opens a call of the method at `path`.
+    pub async fn call<Req, Resp>(
+        &self,
+        path: &str,
+    ) -> Result<(MessageSink<Req>, Streaming<Resp>), ArbError>
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        let (writer, reader) = self.conn.new_arb().into_parts();
+
+        let header = Payload::ArbitaryData(ArbitaryData {
+            content: path.as_bytes().to_vec(),
+        });
+        writer.write(header).await?;
+
+        Ok((MessageSink::new(Arc::new(writer)), Streaming::new(reader)))
+    }
+
+    pub async fn unary<Req, Resp>(&self, path: &str, request: &Req) -> Result<Resp, ArbError>
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        self.server_streaming(path, request).await?.single().await
+    }
+
+    pub async fn server_streaming<Req, Resp>(
+        &self,
+        path: &str,
+        request: &Req,
+    ) -> Result<Streaming<Resp>, ArbError>
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        let (requests, responses) = self.call(path).await?;
+        requests.send(request).await?;
+        requests.close().await?;
+
+        Ok(responses)
+    }
+
+    pub async fn client_streaming<Req, Resp>(
+        &self,
+        path: &str,
+    ) -> Result<ClientStreaming<Req, Resp>, ArbError>
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        let (requests, response) = self.call(path).await?;
+
+        Ok(ClientStreaming { requests, response })
+    }
+
+    pub async fn bidi_streaming<Req, Resp>(
+        &self,
+        path: &str,
+    ) -> Result<(MessageSink<Req>, Streaming<Resp>), ArbError>
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        self.call(path).await
+    }
+}
+
+/// A call received by a [`Service`].
+pub struct ServerCall {
+    reader: ContextReader,
+    writer: Arc<dyn Outbound>,
+}
+
+impl ServerCall {
+    /// Returns the requests and the sink of the responses.
+    pub fn into_parts<Req, Resp>(self) -> (Streaming<Req>, MessageSink<Resp>)
+    where
+        Req: prost::Message + Default,
+        Resp: prost::Message + Default,
+    {
+        (Streaming::new(self.reader), MessageSink::new(self.writer))
+    }
+}
+
+/// A service that can be served by a [`Router`], usually generated by
+/// `protofish-build`.
+#[async_trait]
+pub trait Service: Send + Sync + 'static {
+    /// Returns the full name of the service, e.g. `helloworld.Greeter`.
+    fn name(&self) -> &'static str;
+
+    /// Handles a call of `method`.
+    async fn call(&self, method: &str, call: ServerCall) -> Result<(), Status>;
+}
+
+/// Dispatches the calls of a connection to services.
+#[derive(Clone, Default)]
+pub struct Router {
+    services: HashMap<&'static str, Arc<dyn Service>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_service(mut self, service: impl Service) -> Self {
+        self.services.insert(service.name(), Arc::new(service));
+        self
+    }
+
+    /// Serves calls of the peer until the connection ends.
+    ///
+    /// Each call is handled in its own task.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the connection ended, unless it was closed by either side.
+    pub async fn serve<U: UTP>(&self, conn: &Connection<U>) -> Result<(), ConnectionError> {
+        loop {
+            let arb = match conn.next_arb().await {
+                Ok(arb) => arb,
+                Err(ConnectionError::ClosedStream | ConnectionError::PeerClosed(_)) => {
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+
+            let router = self.clone();
+            let (writer, reader) = arb.into_parts();
+
+            tokio::spawn(async move {
+                router.dispatch(Arc::new(writer), reader).await;
+            });
+        }
+    }
+
+    async fn dispatch(&self, writer: Arc<dyn Outbound>, reader: ContextReader) {
+        let result = match reader.read().await {
+            Ok(Payload::ArbitaryData(header)) => match String::from_utf8(header.content) {
+                Ok(path) => {
+                    let call = ServerCall {
+                        reader,
+                        writer: writer.clone(),
+                    };
+                    self.route(&path, call).await
+                }
+                Err(_) => Err(Status::new(
+                    ErrorType::Malformed,
+                    "method path is not UTF-8",
+                )),
+            },
+            Ok(_) => Err(Status::new(ErrorType::Malformed, "expected a method path")),
+            Err(e) => {
+                tracing::debug!("Call ended before its method path: {}", e);
+                return;
+            }
+        };
+
+        if let Err(status) = result {
+            let error = Payload::Error(ErrorPayload {
+                error_type: status.kind,
+                message: status.message,
+            });
+            if let Err(e) = writer.write(error).await {
+                tracing::debug!("Failed to report call failure: {}", e);
+            }
+        }
+
+        // a handler may still hold a sink; the call ends with the handler
+        let _ = writer.close().await;
+    }
+
+    async fn route(&self, path: &str, call: ServerCall) -> Result<(), Status> {
+        let Some((service, method)) = path.trim_start_matches('/').split_once('/') else {
+            return Err(Status::not_found(format!("invalid method path {}", path)));
+        };
+
+        match self.services.get(service) {
+            Some(service) => service.call(method, call).await,
+            None => Err(Status::not_found(format!("unknown service {}", service))),
+        }
+    }
+}
@@ -1,16 +1,252 @@
-use crate::utp::UTPStream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
 
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::{
+    core::common::context::{ContextWriter, WeakContextWriter},
+    schema::{IntegrityType, Payload, StreamClose, StreamId},
+    utp::UTPStream,
+};
+
+/// A stream opened on a context, readable and writable like a duplex I/O
+/// object.
+///
+/// The peer is told that the stream ended once it is shut down or dropped;
+/// reading continues after a shutdown.
 pub struct ProtofishStream<U: UTPStream> {
-    stream: U,
+    writer: ProtofishStreamWriter<U>,
+    reader: ProtofishStreamReader<U>,
 }
 
 impl<U: UTPStream> ProtofishStream<U> {
-    pub(crate) fn new(stream: U) -> Self {
-        Self { stream }
+    pub(crate) fn new(stream: U, context: ContextWriter<U>) -> Self {
+        let stream_id = stream.id();
+        let integrity = stream.integrity_type();
+        let (writer, reader) = stream.split();
+
+        let reader = ProtofishStreamReader {
+            inner: reader,
+            stream_id,
+            context: context.downgrade(),
+        };
+        let writer = ProtofishStreamWriter {
+            inner: writer,
+            integrity,
+            closer: StreamCloser::new(stream_id, context),
+        };
+
+        Self { writer, reader }
+    }
+
+    /// Returns the ID of this stream.
+    pub fn id(&self) -> StreamId {
+        self.reader.stream_id
+    }
+
+    /// Returns the integrity type this stream was opened with.
+    pub fn integrity_type(&self) -> IntegrityType {
+        self.writer.integrity.clone()
     }
 
+    /// Returns a writer of the context this stream was opened on, which
+    /// keeps the context open like any other clone.
+    pub fn context(&self) -> ContextWriter<U> {
+        self.writer.context()
+    }
+
+    /// Ends the writing half of this stream, keeping the reading half open.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying stream cannot be shut down.
+    pub async fn shutdown_write(&mut self) -> std::io::Result<()> {
+        self.writer.shutdown().await
+    }
+
+    /// Splits the stream into its writing and reading halves.
+    ///
+    /// The peer is told that the stream ended once the writing half is shut
+    /// down or dropped.
     #[inline(always)]
-    pub fn split(self) -> (U::StreamWrite, U::StreamRead) {
-        self.stream.split()
+    pub fn split(self) -> (ProtofishStreamWriter<U>, ProtofishStreamReader<U>) {
+        (self.writer, self.reader)
+    }
+
+    /// Puts the halves returned by [`ProtofishStream::split`] back together.
+    ///
+    /// # Errors
+    ///
+    /// Returns the halves in an [`UnsplitError`] if they belong to different
+    /// streams, e.g. streams with the same ID on different connections.
+    pub fn unsplit(
+        writer: ProtofishStreamWriter<U>,
+        reader: ProtofishStreamReader<U>,
+    ) -> Result<Self, Box<UnsplitError<U>>> {
+        if writer.id() == reader.id() && reader.context.same_context(&writer.closer.context) {
+            Ok(Self { writer, reader })
+        } else {
+            Err(Box::new(UnsplitError(writer, reader)))
+        }
+    }
+}
+
+impl<U: UTPStream> AsyncRead for ProtofishStream<U> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().reader).poll_read(cx, buf)
+    }
+}
+
+impl<U: UTPStream> AsyncWrite for ProtofishStream<U> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut self.get_mut().writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().writer).poll_shutdown(cx)
+    }
+}
+
+/// Error returned by [`ProtofishStream::unsplit`] when the halves belong to
+/// different streams, handing them back.
+#[derive(Error)]
+#[error("tried to unsplit halves of different streams")]
+pub struct UnsplitError<U: UTPStream>(pub ProtofishStreamWriter<U>, pub ProtofishStreamReader<U>);
+
+impl<U: UTPStream> std::fmt::Debug for UnsplitError<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("UnsplitError")
+            .field(&self.0.id())
+            .field(&self.1.id())
+            .finish()
+    }
+}
+
+/// Writing half of a [`ProtofishStream`].
+///
+/// Shutting it down or dropping it sends `StreamClose` on the owning context,
+/// so the peer's reader reaches its end even over transports without a
+/// stream-level FIN.
+pub struct ProtofishStreamWriter<U: UTPStream> {
+    inner: U::StreamWrite,
+    integrity: IntegrityType,
+    closer: StreamCloser<U>,
+}
+
+impl<U: UTPStream> ProtofishStreamWriter<U> {
+    /// Returns the ID of the stream.
+    pub fn id(&self) -> StreamId {
+        self.closer.stream_id
+    }
+
+    /// Returns a writer of the context the stream was opened on, which keeps
+    /// the context open like any other clone.
+    pub fn context(&self) -> ContextWriter<U> {
+        self.closer.context.clone()
+    }
+}
+
+impl<U: UTPStream> AsyncWrite for ProtofishStreamWriter<U> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_shutdown(cx);
+
+        if let Poll::Ready(Ok(())) = result {
+            this.closer.close();
+        }
+
+        result
+    }
+}
+
+/// Reading half of a [`ProtofishStream`].
+pub struct ProtofishStreamReader<U: UTPStream> {
+    inner: U::StreamRead,
+    stream_id: StreamId,
+
+    /// Context the stream was opened on, not kept open by the reader
+    context: WeakContextWriter<U>,
+}
+
+impl<U: UTPStream> ProtofishStreamReader<U> {
+    /// Returns the ID of the stream.
+    pub fn id(&self) -> StreamId {
+        self.stream_id
+    }
+}
+
+impl<U: UTPStream> AsyncRead for ProtofishStreamReader<U> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+/// Sends `StreamClose` for a stream once closed or dropped.
+pub(crate) struct StreamCloser<U: UTPStream> {
+    pub(crate) stream_id: StreamId,
+    pub(crate) context: ContextWriter<U>,
+    closed: bool,
+}
+
+impl<U: UTPStream> StreamCloser<U> {
+    pub(crate) fn new(stream_id: StreamId, context: ContextWriter<U>) -> Self {
+        Self {
+            stream_id,
+            context,
+            closed: false,
+        }
+    }
+
+    fn close(&mut self) {
+        if std::mem::replace(&mut self.closed, true) {
+            return;
+        }
+
+        let stream_id = self.stream_id;
+        self.context
+            .queue(Payload::StreamClose(StreamClose { stream_id }));
+    }
+}
+
+impl<U: UTPStream> Drop for StreamCloser<U> {
+    fn drop(&mut self) {
+        self.close();
     }
 }
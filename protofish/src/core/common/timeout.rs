@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::{
+    core::common::{
+        context::{ContextReader, ContextWriter},
+        error::ConnectionError,
+    },
+    error::ProtofishError,
+    schema::{Error, ErrorType, Payload},
+    utp::UTPStream,
+};
+
+/// Awaits `future`, failing with `ConnectionError::HandshakeTimeout` once
+/// `timeout` elapses. Waits indefinitely if `timeout` is `None`.
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    waiting_for: &str,
+    future: impl Future<Output = Result<T, ProtofishError>>,
+) -> Result<T, ProtofishError> {
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| Err(ConnectionError::HandshakeTimeout(waiting_for.into()).into()))
+}
+
+/// Reads the next payload of a handshake context, telling the peer if the
+/// deadline expires.
+pub(crate) async fn read_with_timeout<S: UTPStream>(
+    tx: &ContextWriter<S>,
+    rx: &ContextReader,
+    timeout: Option<Duration>,
+    waiting_for: &str,
+) -> Result<Payload, ProtofishError> {
+    let result = with_timeout(timeout, waiting_for, async { Ok(rx.read().await?) }).await;
+
+    if let Err(ProtofishError::Connection(ConnectionError::HandshakeTimeout(_))) = &result {
+        notify_timeout(tx, waiting_for).await?;
+    }
+
+    result
+}
+
+/// Tells the peer that we stopped waiting for it.
+pub(crate) async fn notify_timeout<S: UTPStream>(
+    tx: &ContextWriter<S>,
+    waiting_for: &str,
+) -> Result<(), ProtofishError> {
+    tx.write(timeout_error(waiting_for)).await?;
+
+    Ok(())
+}
+
+/// Returns the `Error` telling the peer that we stopped waiting for it.
+pub(crate) fn timeout_error(waiting_for: &str) -> Payload {
+    Payload::Error(Error {
+        error_type: ErrorType::Timeout,
+        message: format!("Timed out waiting for {waiting_for}."),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        core::common::{error::ConnectionError, timeout::with_timeout},
+        error::ProtofishError,
+    };
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let result = with_timeout(Some(Duration::from_millis(10)), "nothing", async {
+            std::future::pending::<Result<(), ProtofishError>>().await
+        })
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(ProtofishError::Connection(
+                ConnectionError::HandshakeTimeout(_)
+            ))
+        ));
+
+        let result = with_timeout(None, "something", async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+}
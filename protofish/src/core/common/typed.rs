@@ -0,0 +1,93 @@
+use std::marker::PhantomData;
+
+use crate::{
+    core::common::{
+        arbitrary::{ArbContext, ArbError},
+        codec::Codec,
+    },
+    schema::ErrorType,
+    utp::UTP,
+};
+
+/// An arbitrary data context carrying typed messages.
+///
+/// Writes messages of type `Req` and reads messages of type `Resp`, each
+/// encoded with the codec `C` into one `ArbitaryData` payload. Both sides
+/// name the types from their own point of view, so the side accepting the
+/// context swaps them:
+///
+/// ```no_run
+/// # use protofish::{Connection, TypedContext, codec::Codec, utp::UTP};
+/// # async fn example<U: UTP, C: Codec<String> + Codec<u64>>(
+/// #     client: Connection<U>,
+/// #     server: Connection<U>,
+/// # ) {
+/// let request: TypedContext<String, u64, C, U> = client.new_typed();
+/// let response: TypedContext<u64, String, C, U> =
+///     server.next_typed::<u64, String, C>().await.unwrap();
+/// # }
+/// ```
+pub struct TypedContext<Req, Resp, C, U: UTP> {
+    arb: ArbContext<U>,
+    _marker: PhantomData<fn(Req) -> (Resp, C)>,
+}
+
+impl<Req, Resp, C, U> TypedContext<Req, Resp, C, U>
+where
+    C: Codec<Req> + Codec<Resp>,
+    U: UTP,
+{
+    pub fn new(arb: ArbContext<U>) -> Self {
+        Self {
+            arb,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Encodes and writes a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArbError::Codec` if the message cannot be encoded, or an
+    /// error if the underlying write operation fails.
+    pub async fn write(&self, message: &Req) -> Result<(), ArbError> {
+        let buf = <C as Codec<Req>>::encode(message)?;
+
+        self.arb.write(buf).await
+    }
+
+    /// Reads and decodes the next message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArbError::Codec` if the received bytes are not a valid
+    /// message, or the errors of [`ArbContext::read`].
+    pub async fn read(&self) -> Result<Resp, ArbError> {
+        let buf = self.arb.read().await?;
+
+        Ok(<C as Codec<Resp>>::decode(buf)?)
+    }
+
+    /// Reports a failure to the peer, see [`ArbContext::fail`].
+    pub async fn fail(
+        &self,
+        error_type: ErrorType,
+        message: impl Into<String>,
+    ) -> Result<(), ArbError> {
+        self.arb.fail(error_type, message).await
+    }
+
+    /// Ends this context, see [`ArbContext::close`].
+    pub async fn close(&self) -> Result<(), ArbError> {
+        self.arb.close().await
+    }
+
+    /// Returns the untyped context, e.g. to open streams.
+    pub fn arb(&self) -> &ArbContext<U> {
+        &self.arb
+    }
+
+    pub fn into_inner(self) -> ArbContext<U> {
+        self.arb
+    }
+}
@@ -0,0 +1,47 @@
+use crate::schema::Version;
+
+/// Negotiates the protocol version used with a peer.
+///
+/// Versions with different major numbers are incompatible. Otherwise the lower
+/// of both versions is used, so that features of a newer minor version are
+/// only enabled when both sides support them.
+///
+/// # Returns
+///
+/// Returns the negotiated version, or `None` if the versions are incompatible.
+pub fn negotiate_version(local: &Version, remote: &Version) -> Option<Version> {
+    if local.major == remote.major {
+        Some(local.clone().min(remote.clone()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::common::version::negotiate_version, schema::Version};
+
+    fn version(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(
+            negotiate_version(&version(1, 2, 0), &version(1, 1, 5)),
+            Some(version(1, 1, 5))
+        );
+        assert_eq!(
+            negotiate_version(&version(1, 0, 1), &version(1, 0, 3)),
+            Some(version(1, 0, 1))
+        );
+        assert_eq!(
+            negotiate_version(&version(1, 0, 0), &version(2, 0, 0)),
+            None
+        );
+    }
+}
@@ -1,27 +1,148 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::{
     IntegrityType,
+    constant::DEFAULT_HANDSHAKE_TIMEOUT,
     core::{
-        common::{connection::Connection, error::ConnectionError, pmc::PMC},
-        server::handshake::server_handshake,
+        common::{
+            benchmark::spawn_responder, connection::Connection, error::ConnectionError,
+            frame::FrameOptions, keepalive::KeepaliveOptions, pmc::PMC, queue::QueueOptions,
+            timeout::with_timeout,
+        },
+        server::{auth::Authenticator, handshake::server_handshake, store::ConnectionTokenStore},
     },
     error::ProtofishError,
+    schema::Extensions,
     utp::{UTP, UTPEvent},
 };
 
+/// Options for accepting Protofish connections.
+#[derive(Clone)]
+pub struct AcceptOptions {
+    /// Store recording issued connection tokens, used for resumption and lookups.
+    ///
+    /// Resumption is rejected when no store is configured.
+    pub token_store: Option<Arc<dyn ConnectionTokenStore>>,
+
+    /// Extensions supported by the server.
+    pub extensions: Extensions,
+
+    /// Authenticator deciding whether a client is accepted.
+    ///
+    /// Every client is accepted without a principal when none is configured.
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+
+    /// Deadline for the client to open the PMC stream.
+    ///
+    /// `None` waits indefinitely.
+    pub stream_timeout: Option<Duration>,
+
+    /// Deadline for each client message during the handshake, i.e. the
+    /// `ClientHello` and any `AuthResponse`.
+    ///
+    /// `None` waits indefinitely.
+    pub hello_timeout: Option<Duration>,
+
+    /// Heartbeat settings; keepalives are not sent when `None`.
+    pub keepalive: Option<KeepaliveOptions>,
+
+    /// Limits of the queues of incoming payloads.
+    pub queue: QueueOptions,
+
+    /// Largest frame size and handling of undecodable frames.
+    pub frame: FrameOptions,
+
+    /// Whether benchmark runs of clients are answered.
+    pub benchmark_responder: bool,
+}
+
+impl Default for AcceptOptions {
+    fn default() -> Self {
+        Self {
+            token_store: None,
+            extensions: Extensions::default(),
+            authenticator: None,
+            stream_timeout: Some(DEFAULT_HANDSHAKE_TIMEOUT),
+            hello_timeout: Some(DEFAULT_HANDSHAKE_TIMEOUT),
+            keepalive: None,
+            queue: QueueOptions::default(),
+            frame: FrameOptions::default(),
+            benchmark_responder: false,
+        }
+    }
+}
+
+impl AcceptOptions {
+    pub fn with_token_store(mut self, token_store: Arc<dyn ConnectionTokenStore>) -> Self {
+        self.token_store = Some(token_store);
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn with_authenticator(mut self, authenticator: Arc<dyn Authenticator>) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    pub fn with_stream_timeout(mut self, stream_timeout: Duration) -> Self {
+        self.stream_timeout = Some(stream_timeout);
+        self
+    }
+
+    pub fn with_hello_timeout(mut self, hello_timeout: Duration) -> Self {
+        self.hello_timeout = Some(hello_timeout);
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: KeepaliveOptions) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    pub fn with_queue(mut self, queue: QueueOptions) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    pub fn with_frame(mut self, frame: FrameOptions) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    pub fn with_benchmark_responder(mut self) -> Self {
+        self.benchmark_responder = true;
+        self
+    }
+}
+
 /// Accepts an incoming Protofish connection as a server.
 ///
+/// This is [`accept_with`] using the default [`AcceptOptions`].
+pub async fn accept<U>(utp: Arc<U>) -> Result<Connection<U>, ProtofishError>
+where
+    U: UTP,
+{
+    accept_with(utp, &AcceptOptions::default()).await
+}
+
+/// Accepts an incoming Protofish connection as a server with the given options.
+///
 /// This function waits for a new stream from a client and performs the
 /// server-side handshake. It will:
 /// 1. Wait for a `NewStream` event from the UTP layer
 /// 2. Create a Primary Messaging Channel (PMC) for the stream
-/// 3. Perform the server-side handshake by receiving `ClientHello` and sending `ServerHello`
+/// 3. Perform the server-side handshake by receiving `ClientHello`, authenticating
+///    the client and sending `ServerHello`
 /// 4. Return a `Connection` if the handshake succeeds
 ///
 /// # Arguments
 ///
 /// * `utp` - An Arc-wrapped UTP implementation for the underlying transport
+/// * `options` - Server-side options such as the token store and authenticator
 ///
 /// # Returns
 ///
@@ -33,35 +154,73 @@ use crate::{
 /// This function will return an error if:
 /// - The UTP event is not a `NewStream`
 /// - Waiting for the stream fails
-/// - The handshake validation fails
-pub async fn accept<U>(utp: Arc<U>) -> Result<Connection<U>, ProtofishError>
+/// - The handshake validation fails or the client is rejected
+/// - The client does not respond within the configured deadlines, in which
+///   case `ConnectionError::HandshakeTimeout` is returned
+pub async fn accept_with<U>(
+    utp: Arc<U>,
+    options: &AcceptOptions,
+) -> Result<Connection<U>, ProtofishError>
 where
     U: UTP,
 {
-    let event = utp.next_event().await; // TODO maybe timeout
+    let stream = with_timeout(options.stream_timeout, "the PMC stream", async {
+        if let UTPEvent::NewStream(id) = utp.next_event().await {
+            Ok(utp.wait_stream(id, IntegrityType::Reliable).await?)
+        } else {
+            Err(ConnectionError::ClosedStream.into())
+        }
+    })
+    .await?;
 
-    if let UTPEvent::NewStream(id) = event {
-        let stream = utp.wait_stream(id, IntegrityType::Reliable).await?;
-        let pmc = PMC::new(true, stream);
+    let mut pmc = PMC::with_limits(
+        true,
+        stream,
+        options.queue.clone(),
+        options.frame.max_frame_size,
+        options.frame.decode_policy,
+    );
 
-        server_handshake(&pmc).await?;
+    // installed before the handshake, so no run can slip past it
+    if options.benchmark_responder {
+        spawn_responder(&utp, &pmc);
+    }
+
+    let outcome = server_handshake(&mut pmc, options, &utp.identity()).await?;
 
-        Ok(Connection::new(utp.clone(), pmc))
-    } else {
-        Err(ConnectionError::ClosedStream.into())
+    let mut connection = Connection::from_handshake(utp.clone(), pmc, outcome);
+
+    if let Some(keepalive) = &options.keepalive {
+        connection.start_keepalive(keepalive);
     }
+
+    Ok(connection)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use crate::{
         constant::VERSION,
-        core::{common::pmc::PMC, server::accept},
-        schema::{ClientHello, IntegrityType, Payload},
+        core::{
+            common::{error::ConnectionError, pmc::PMC},
+            server::{AcceptOptions, accept, accept_with},
+        },
+        error::ProtofishError,
+        schema::{ClientHello, ErrorType, IntegrityType, Payload, Version},
         utp::{UTP, tests::utp::mock_utp_pairs},
     };
 
     async fn imitate_handshake(resume_connection_token: Option<Vec<u8>>, assert_ok: bool) {
+        imitate_handshake_with_version(VERSION, resume_connection_token, assert_ok).await;
+    }
+
+    async fn imitate_handshake_with_version(
+        version: Version,
+        resume_connection_token: Option<Vec<u8>>,
+        assert_ok: bool,
+    ) {
         let (a, b) = mock_utp_pairs();
 
         tokio::spawn(async move {
@@ -71,9 +230,11 @@ mod tests {
             let (tx, rx) = pmc.create_context();
 
             let client_hello = ClientHello {
-                version: VERSION,
+                version,
                 resume_connection_token,
                 hostname: "example.com".to_string(),
+                extensions: Default::default(),
+                credentials: None,
             };
 
             tx.write(Payload::ClientHello(client_hello)).await.unwrap();
@@ -86,7 +247,7 @@ mod tests {
             }
         });
 
-        accept(a.into()).await.unwrap();
+        assert_eq!(accept(a.into()).await.is_ok(), assert_ok);
     }
 
     #[tokio::test]
@@ -98,4 +259,63 @@ mod tests {
     async fn test_server_accept_fail() {
         imitate_handshake(Some(vec![]), false).await;
     }
+
+    #[tokio::test]
+    async fn test_server_accept_minor_version() {
+        let version = Version {
+            minor: VERSION.minor + 1,
+            ..VERSION
+        };
+        imitate_handshake_with_version(version, None, true).await;
+    }
+
+    #[tokio::test]
+    async fn test_server_accept_major_version_mismatch() {
+        let version = Version {
+            major: VERSION.major + 1,
+            ..VERSION
+        };
+        imitate_handshake_with_version(version, None, false).await;
+    }
+
+    fn assert_timeout<T>(result: Result<T, ProtofishError>) {
+        assert!(matches!(
+            result,
+            Err(ProtofishError::Connection(
+                ConnectionError::HandshakeTimeout(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_accept_stream_timeout() {
+        let (a, _b) = mock_utp_pairs();
+        let options = AcceptOptions::default().with_stream_timeout(Duration::from_millis(20));
+
+        assert_timeout(accept_with(a.into(), &options).await);
+    }
+
+    #[tokio::test]
+    async fn test_server_accept_hello_timeout() {
+        let (a, b) = mock_utp_pairs();
+        let options = AcceptOptions::default().with_hello_timeout(Duration::from_millis(20));
+
+        let client = tokio::spawn(async move {
+            let stream = b.new_stream(IntegrityType::Reliable).await.unwrap();
+            let pmc = PMC::new(false, stream);
+
+            let (_tx, rx) = pmc.create_context();
+            rx.read().await
+        });
+
+        assert_timeout(accept_with(a.into(), &options).await);
+
+        // reported on the control context, which ends the connection
+        let result = client.await.unwrap();
+        assert!(matches!(
+            result,
+            Err(ConnectionError::PeerError { kind: ErrorType::Timeout, message })
+                if message.contains("ClientHello")
+        ));
+    }
 }
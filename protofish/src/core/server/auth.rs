@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+
+use crate::{
+    schema::{AuthChallenge, AuthResponse, ClientHello},
+    utp::TransportIdentity,
+};
+
+/// The authenticated identity of a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Name identifying the client, e.g. a user or service name
+    pub name: String,
+
+    /// Additional attributes such as roles or tenant IDs
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Principal {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Outcome of authenticating a client.
+#[derive(Debug, Clone)]
+pub enum AuthDecision {
+    /// The client is accepted as the given principal
+    Accept(Principal),
+
+    /// The client is rejected; the message is sent back in the `ServerHello`
+    Reject(String),
+
+    /// The client must answer the challenge before a decision is made
+    Challenge(AuthChallenge),
+}
+
+/// Trait for authenticating clients during the handshake.
+///
+/// The authenticator is called for every `ClientHello`, including ones that
+/// resume a connection, before the `ServerHello` is sent.
+///
+/// Returning [`AuthDecision::Challenge`] sends an `AuthChallenge` on the
+/// handshake context; the client's `AuthResponse` is passed to
+/// [`Authenticator::verify`], which may issue further challenges.
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    /// Decides whether the client is accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `hello` - The `ClientHello` including hostname and credentials
+    /// * `identity` - The identity of the peer as reported by the UTP
+    async fn authenticate(&self, hello: &ClientHello, identity: &TransportIdentity)
+    -> AuthDecision;
+
+    /// Decides on a client's answer to a previously issued challenge.
+    ///
+    /// The default implementation rejects every response.
+    ///
+    /// # Arguments
+    ///
+    /// * `hello` - The `ClientHello` the exchange started with
+    /// * `identity` - The identity of the peer as reported by the UTP
+    /// * `challenge` - The challenge the client answered
+    /// * `response` - The client's answer
+    async fn verify(
+        &self,
+        hello: &ClientHello,
+        identity: &TransportIdentity,
+        challenge: &AuthChallenge,
+        response: &AuthResponse,
+    ) -> AuthDecision {
+        let _ = (hello, identity, challenge, response);
+        AuthDecision::Reject("Challenge-response authentication is not supported.".into())
+    }
+}
@@ -1,34 +1,147 @@
 use bytes::Bytes;
 
 use crate::{
-    constant::VERSION,
+    constant::{CONTROL_CONTEXT_ID, MAX_AUTH_ROUNDS, VERSION},
     core::{
-        common::{context::Context, error::ConnectionError, pmc::PMC},
-        server::token::generate_connection_token,
+        common::{
+            connection::HandshakeOutcome,
+            context::Context,
+            error::ConnectionError,
+            extension::negotiate_extensions,
+            pmc::PMC,
+            timeout::{read_with_timeout, timeout_error, with_timeout},
+            version::negotiate_version,
+        },
+        server::{
+            accept::AcceptOptions, auth::AuthDecision, store::ConnectionRecord,
+            token::generate_connection_token,
+        },
     },
     error::ProtofishError,
-    schema::{Payload, ServerHello},
-    utp::UTPStream,
+    schema::{Extensions, Message, Payload, ServerHello},
+    utp::{TransportIdentity, UTPStream},
 };
 
-pub async fn server_handshake<S: UTPStream>(pmc: &PMC<S>) -> Result<(), ProtofishError> {
-    let ctx = get_client_hello(pmc).await?;
-    let payload = ctx.1.read().await?;
+/// Performs the server side of the handshake.
+///
+/// The client is rejected if its major protocol version differs from ours or
+/// the configured authenticator rejects it. Challenges issued by the
+/// authenticator are exchanged on the handshake context before the
+/// `ServerHello`. A fresh connection is recorded in the token store if given.
+/// A `ClientHello` carrying a valid resume token gets its token back and `pmc`
+/// continues with the context ID counter of the resumed connection, if the
+/// store kept it.
+pub async fn server_handshake<S: UTPStream>(
+    pmc: &mut PMC<S>,
+    options: &AcceptOptions,
+    identity: &TransportIdentity,
+) -> Result<HandshakeOutcome, ProtofishError> {
+    let (ctx, payload) = get_client_hello(pmc, options).await?;
 
     if let Payload::ClientHello(client_hello) = payload {
-        if client_hello.resume_connection_token.is_some() {
-            reject_client(ctx, "Resume connection is not supported.").await
+        let Some(version) = negotiate_version(&VERSION, &client_hello.version) else {
+            let message = format!(
+                "Incompatible protocol version {}, server speaks {}.",
+                client_hello.version, VERSION
+            );
+            reject_client(ctx, message).await?;
+
+            return Err(ConnectionError::IncompatibleVersion {
+                local: VERSION,
+                remote: client_hello.version,
+            }
+            .into());
+        };
+
+        let principal = if let Some(authenticator) = &options.authenticator {
+            let mut decision = authenticator.authenticate(&client_hello, identity).await;
+            let mut rounds = 0;
+
+            loop {
+                match decision {
+                    AuthDecision::Accept(principal) => break Some(principal),
+                    AuthDecision::Reject(message) => return reject_with(ctx, &message).await,
+                    AuthDecision::Challenge(_) if rounds == MAX_AUTH_ROUNDS => {
+                        return reject_with(ctx, "Too many authentication rounds.").await;
+                    }
+                    AuthDecision::Challenge(challenge) => {
+                        rounds += 1;
+                        ctx.0
+                            .write(Payload::AuthChallenge(challenge.clone()))
+                            .await?;
+
+                        let payload = read_with_timeout(
+                            &ctx.0,
+                            &ctx.1,
+                            options.hello_timeout,
+                            "AuthResponse",
+                        )
+                        .await?;
+
+                        let Payload::AuthResponse(response) = payload else {
+                            return reject_with(ctx, "Expected AuthResponse.").await;
+                        };
+
+                        decision = authenticator
+                            .verify(&client_hello, identity, &challenge, &response)
+                            .await;
+                    }
+                }
+            }
         } else {
-            accept_client(ctx, generate_connection_token()).await
-        }
+            None
+        };
+
+        let token_store = options.token_store.as_deref();
+        let token = match (client_hello.resume_connection_token, token_store) {
+            (None, None) => generate_connection_token(),
+            (None, Some(token_store)) => {
+                let token = token_store.generate_token();
+                let record = ConnectionRecord::with_counter(pmc.counter(), principal.clone());
+                token_store.insert(token.clone(), record).await;
+
+                token
+            }
+            (Some(token), Some(token_store)) => {
+                if let Some(record) = token_store.lookup(&token).await {
+                    if record.principal.is_some() && record.principal != principal {
+                        return reject_with(ctx, "Connection token of another principal.").await;
+                    }
+
+                    pmc.resume(record.counter);
+
+                    token.into()
+                } else {
+                    return reject_with(ctx, "Unknown or expired connection token.").await;
+                }
+            }
+            (Some(_), None) => {
+                return reject_with(ctx, "Resume connection is not supported.").await;
+            }
+        };
+
+        let extensions = negotiate_extensions(
+            &client_hello.extensions,
+            &options.extensions.advertised(options.frame.max_frame_size),
+        );
+
+        accept_client(ctx, token.clone(), extensions.clone()).await?;
+
+        Ok(HandshakeOutcome {
+            token,
+            version,
+            extensions,
+            principal,
+        })
     } else {
-        Err(ConnectionError::MalformedPayload("expected ClientHello".into(), payload).into())
+        Err(ConnectionError::MalformedPayload("expected ClientHello".into(), payload.into()).into())
     }
 }
 
 async fn accept_client<S: UTPStream>(
     ctx: Context<S>,
     connection_token: Bytes,
+    extensions: Extensions,
 ) -> Result<(), ProtofishError> {
     let (tx, _) = ctx;
 
@@ -38,6 +151,7 @@ async fn accept_client<S: UTPStream>(
         connection_token: Some(connection_token),
 
         message: None,
+        extensions,
     };
 
     tx.write(Payload::ServerHello(server_hello)).await?;
@@ -45,14 +159,18 @@ async fn accept_client<S: UTPStream>(
     Ok(())
 }
 
-async fn reject_client<S: UTPStream>(ctx: Context<S>, message: &str) -> Result<(), ProtofishError> {
+async fn reject_client<S: UTPStream>(
+    ctx: Context<S>,
+    message: String,
+) -> Result<(), ProtofishError> {
     let (tx, _) = ctx;
 
     let server_hello = ServerHello {
         version: VERSION,
         ok: false,
         connection_token: None,
-        message: Some(message.into()),
+        message: Some(message),
+        extensions: Extensions::default(),
     };
 
     tx.write(Payload::ServerHello(server_hello)).await?;
@@ -60,8 +178,36 @@ async fn reject_client<S: UTPStream>(ctx: Context<S>, message: &str) -> Result<(
     Ok(())
 }
 
-async fn get_client_hello<S: UTPStream>(pmc: &PMC<S>) -> Result<Context<S>, ProtofishError> {
-    pmc.next_context()
-        .await
-        .ok_or(ProtofishError::from(ConnectionError::ClosedStream))
+async fn reject_with<S: UTPStream, T>(ctx: Context<S>, message: &str) -> Result<T, ProtofishError> {
+    reject_client(ctx, message.into()).await?;
+
+    Err(ConnectionError::HandshakeReject(message.into()).into())
+}
+
+/// Waits for the context opened by the client along with its `ClientHello`.
+///
+/// Without a handshake context, the client is told about an expired deadline
+/// on the control context.
+async fn get_client_hello<S: UTPStream>(
+    pmc: &PMC<S>,
+    options: &AcceptOptions,
+) -> Result<(Context<S>, Payload), ProtofishError> {
+    let result = with_timeout(options.hello_timeout, "ClientHello", async {
+        let ctx = pmc.next_context().await?;
+        let payload = ctx.1.read().await?;
+
+        Ok((ctx, payload))
+    })
+    .await;
+
+    if let Err(ProtofishError::Connection(ConnectionError::HandshakeTimeout(_))) = &result {
+        pmc.frame()
+            .send_frame(Message {
+                context_id: CONTROL_CONTEXT_ID,
+                payload: timeout_error("ClientHello"),
+            })
+            .await?;
+    }
+
+    result
 }
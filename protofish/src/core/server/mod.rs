@@ -1,5 +1,15 @@
 mod accept;
 pub use accept::*;
 
+mod auth;
+pub use auth::{AuthDecision, Authenticator, Principal};
+
 mod handshake;
+
+mod stateless;
+pub use stateless::HmacTokenStore;
+
+mod store;
+pub use store::{ConnectionRecord, ConnectionTokenStore, MemoryTokenStore};
+
 mod token;
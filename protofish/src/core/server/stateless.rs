@@ -0,0 +1,169 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bytes::{BufMut, Bytes, BytesMut};
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use rand::{RngCore, rng};
+use sha2::Sha256;
+
+use crate::core::server::store::{ConnectionRecord, ConnectionTokenStore};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 16;
+const BODY_LEN: usize = size_of::<u64>() + NONCE_LEN;
+const TAG_LEN: usize = 32;
+const TOKEN_LEN: usize = BODY_LEN + TAG_LEN;
+
+/// Stateless token store backed by HMAC-SHA256 signed tokens.
+///
+/// A token consists of its issue time, a random nonce and a signature over
+/// both, so every server sharing the key can validate tokens issued by any
+/// other server without shared state. Tokens are valid for the TTL after
+/// they were issued.
+///
+/// Since no connection state is stored, resumed connections start their
+/// server-initiated context IDs over, and tokens are not bound to the principal
+/// they were issued to. [`expire`](ConnectionTokenStore::expire)
+/// revokes a token on this store only, until the token would have expired
+/// anyway; other servers sharing the key still accept it.
+pub struct HmacTokenStore {
+    key: Vec<u8>,
+    ttl: Duration,
+    revoked: DashMap<Bytes, SystemTime>,
+}
+
+impl HmacTokenStore {
+    /// Creates a store signing tokens with `key`, valid for `ttl` after issue.
+    pub fn new(key: impl Into<Vec<u8>>, ttl: Duration) -> Self {
+        Self {
+            key: key.into(),
+            ttl,
+            revoked: DashMap::new(),
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        // HMAC accepts keys of any length
+        HmacSha256::new_from_slice(&self.key).expect("invalid HMAC key")
+    }
+
+    /// Returns the time a correctly signed token expires at.
+    fn verify(&self, token: &[u8]) -> Option<SystemTime> {
+        if token.len() != TOKEN_LEN {
+            return None;
+        }
+
+        let (body, tag) = token.split_at(BODY_LEN);
+
+        let mut mac = self.mac();
+        mac.update(body);
+        mac.verify_slice(tag).ok()?;
+
+        let issued_at_secs = u64::from_le_bytes(body[..size_of::<u64>()].try_into().ok()?);
+
+        Some(UNIX_EPOCH + Duration::from_secs(issued_at_secs) + self.ttl)
+    }
+}
+
+#[async_trait]
+impl ConnectionTokenStore for HmacTokenStore {
+    fn generate_token(&self) -> Bytes {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rng().fill_bytes(&mut nonce);
+
+        let mut token = BytesMut::with_capacity(TOKEN_LEN);
+        token.put_u64_le(issued_at);
+        token.put_slice(&nonce);
+
+        let mut mac = self.mac();
+        mac.update(&token);
+        token.put_slice(&mac.finalize().into_bytes());
+
+        token.freeze()
+    }
+
+    async fn insert(&self, _token: Bytes, _record: ConnectionRecord) {}
+
+    async fn lookup(&self, token: &[u8]) -> Option<ConnectionRecord> {
+        let expires_at = self.verify(token)?;
+
+        if expires_at <= SystemTime::now() || self.revoked.contains_key(token) {
+            return None;
+        }
+
+        Some(ConnectionRecord::new(expires_at - self.ttl))
+    }
+
+    async fn expire(&self, token: &[u8]) {
+        let Some(expires_at) = self.verify(token) else {
+            return;
+        };
+
+        // a revoked token only needs remembering until it expires
+        let now = SystemTime::now();
+        self.revoked.retain(|_, expires_at| *expires_at > now);
+
+        if expires_at > now {
+            self.revoked
+                .insert(Bytes::copy_from_slice(token), expires_at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::core::server::{stateless::HmacTokenStore, store::ConnectionTokenStore};
+
+    #[tokio::test]
+    async fn test_hmac_store_shared_key() {
+        let issuer = HmacTokenStore::new(b"fleet key".to_vec(), Duration::from_secs(60));
+        let validator = HmacTokenStore::new(b"fleet key".to_vec(), Duration::from_secs(60));
+
+        let token = issuer.generate_token();
+
+        assert!(validator.lookup(&token).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hmac_store_reject() {
+        let store = HmacTokenStore::new(b"fleet key".to_vec(), Duration::from_secs(60));
+        let other = HmacTokenStore::new(b"other key".to_vec(), Duration::from_secs(60));
+
+        let token = store.generate_token();
+        let mut tampered = token.to_vec();
+        tampered[0] ^= 1;
+
+        assert!(other.lookup(&token).await.is_none());
+        assert!(store.lookup(&tampered).await.is_none());
+        assert!(store.lookup(&token[1..]).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hmac_store_revoke() {
+        let store = HmacTokenStore::new(b"fleet key".to_vec(), Duration::from_secs(60));
+
+        let token = store.generate_token();
+        store.expire(&token).await;
+
+        assert!(store.lookup(&token).await.is_none());
+        assert!(store.lookup(&store.generate_token()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hmac_store_expired() {
+        let store = HmacTokenStore::new(b"fleet key".to_vec(), Duration::ZERO);
+
+        let token = store.generate_token();
+
+        assert!(store.lookup(&token).await.is_none());
+    }
+}
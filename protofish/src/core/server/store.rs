@@ -0,0 +1,208 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::core::{
+    common::counter::ContextCounter,
+    server::{auth::Principal, token::generate_connection_token},
+};
+
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Server-side record of a connection, looked up by its connection token.
+#[derive(Clone)]
+pub struct ConnectionRecord {
+    /// Time at which the connection token was issued.
+    pub issued_at: SystemTime,
+
+    /// Principal the connection was authenticated as.
+    ///
+    /// Only the same principal may resume the connection. Records without
+    /// one, e.g. of stores that keep no state, resume under any principal.
+    pub principal: Option<Principal>,
+
+    pub(crate) counter: Option<Arc<Mutex<ContextCounter>>>,
+}
+
+impl ConnectionRecord {
+    /// Creates a record without live connection state.
+    ///
+    /// A connection resumed from such a record keeps its identity, but
+    /// server-initiated context IDs start over.
+    pub fn new(issued_at: SystemTime) -> Self {
+        Self {
+            issued_at,
+            principal: None,
+            counter: None,
+        }
+    }
+
+    pub(crate) fn with_counter(
+        counter: Arc<Mutex<ContextCounter>>,
+        principal: Option<Principal>,
+    ) -> Self {
+        Self {
+            issued_at: SystemTime::now(),
+            principal,
+            counter: Some(counter),
+        }
+    }
+}
+
+/// Trait defining storage of connection tokens issued by a server.
+///
+/// The store is consulted on every handshake: a fresh connection gets a token
+/// from [`generate_token`](Self::generate_token) which is then
+/// [`insert`](Self::insert)ed, and a `ClientHello` carrying a resume token is
+/// accepted only if [`lookup`](Self::lookup) returns a record.
+#[async_trait]
+pub trait ConnectionTokenStore: Send + Sync + 'static {
+    /// Generates a token for a new connection.
+    ///
+    /// Defaults to 32 random bytes.
+    fn generate_token(&self) -> Bytes {
+        generate_connection_token()
+    }
+
+    /// Records a freshly issued token.
+    async fn insert(&self, token: Bytes, record: ConnectionRecord);
+
+    /// Returns the record of a valid, unexpired token.
+    async fn lookup(&self, token: &[u8]) -> Option<ConnectionRecord>;
+
+    /// Invalidates a token so that it can no longer be resumed.
+    async fn expire(&self, token: &[u8]);
+}
+
+struct MemoryEntry {
+    record: ConnectionRecord,
+    expires_at: Instant,
+}
+
+/// In-memory token store with a time-to-live.
+///
+/// Tokens are kept for the TTL after they were issued; looking a token up
+/// does not extend it. Resuming a session from this store also continues
+/// the server-side context IDs where they stopped.
+pub struct MemoryTokenStore {
+    entries: DashMap<Bytes, MemoryEntry>,
+    ttl: Duration,
+}
+
+impl MemoryTokenStore {
+    /// Creates a store that keeps tokens for `ttl` after they were issued.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Returns the number of tokens currently recorded, including expired
+    /// ones that were not purged yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no token is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every expired token.
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        self.entries.retain(|_, entry| entry.expires_at > now);
+    }
+}
+
+impl Default for MemoryTokenStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOKEN_TTL)
+    }
+}
+
+#[async_trait]
+impl ConnectionTokenStore for MemoryTokenStore {
+    async fn insert(&self, token: Bytes, record: ConnectionRecord) {
+        self.entries.insert(
+            token,
+            MemoryEntry {
+                record,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+    }
+
+    async fn lookup(&self, token: &[u8]) -> Option<ConnectionRecord> {
+        let now = Instant::now();
+
+        let entry = self.entries.get(token)?;
+        if entry.expires_at <= now {
+            drop(entry);
+            self.entries.remove(token);
+            return None;
+        }
+
+        Some(entry.record.clone())
+    }
+
+    async fn expire(&self, token: &[u8]) {
+        self.entries.remove(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+
+    use crate::core::{
+        common::counter::ContextCounter,
+        server::store::{ConnectionRecord, ConnectionTokenStore, MemoryTokenStore},
+    };
+
+    #[tokio::test]
+    async fn test_memory_store_ttl() {
+        let store = MemoryTokenStore::new(Duration::from_millis(60));
+        let token = Bytes::from_static(b"token");
+
+        let counter = Arc::new(ContextCounter::new(true).into());
+        store
+            .insert(token.clone(), ConnectionRecord::with_counter(counter, None))
+            .await;
+        assert!(store.lookup(&token).await.is_some());
+
+        // lookups do not extend the TTL
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(store.lookup(&token).await.is_some());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        assert!(store.lookup(&token).await.is_none());
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expire() {
+        let store = MemoryTokenStore::default();
+        let token = store.generate_token();
+
+        store
+            .insert(
+                token.clone(),
+                ConnectionRecord::new(std::time::SystemTime::now()),
+            )
+            .await;
+        store.expire(&token).await;
+
+        assert!(store.lookup(&token).await.is_none());
+    }
+}
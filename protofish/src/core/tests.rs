@@ -1,6 +1,30 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::io::AsyncWriteExt;
+
 use crate::{
-    core::{client::connect, server::accept},
-    utp::tests::utp::mock_utp_pairs,
+    core::{
+        client::{ConnectOptions, CredentialProvider, connect, connect_with, resume},
+        common::{
+            arbitrary::{ArbContext, ArbError, ArbEvent, ArbEventKind},
+            error::ConnectionError,
+            frame::FrameOptions,
+            keepalive::KeepaliveOptions,
+        },
+        server::{
+            AcceptOptions, AuthDecision, Authenticator, HmacTokenStore, MemoryTokenStore,
+            Principal, accept, accept_with,
+        },
+    },
+    error::ProtofishError,
+    schema::{
+        AuthChallenge, AuthResponse, ClientHello, ErrorType, Extensions, IntegrityType, Payload,
+    },
+    utp::{TransportIdentity, tests::utp::mock_utp_pairs},
 };
 
 #[tokio::test]
@@ -13,3 +37,941 @@ async fn test_bi_handshake() {
 
     connect(a.into(), "".into()).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_resume() {
+    let options = AcceptOptions::default().with_token_store(Arc::new(MemoryTokenStore::default()));
+
+    let (a, b) = mock_utp_pairs();
+    let server_options = options.clone();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+    assert_eq!(
+        client_conn.connection_token(),
+        server_conn.connection_token()
+    );
+
+    let client_context_id = client_conn.pmc.create_context().0.context_id;
+    let server_context_id = server_conn.pmc.create_context().0.context_id;
+
+    let (a, b) = mock_utp_pairs();
+    let server_options = options.clone();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    let resumed_client = resume(a.into(), "", client_conn.resume_token())
+        .await
+        .unwrap();
+    let resumed_server = server.await.unwrap().unwrap();
+
+    assert_eq!(
+        resumed_client.connection_token(),
+        client_conn.connection_token()
+    );
+    assert_eq!(
+        resumed_server.connection_token(),
+        server_conn.connection_token()
+    );
+    assert_eq!(
+        resumed_client.pmc.create_context().0.context_id,
+        client_context_id + 2
+    );
+    assert_eq!(
+        resumed_server.pmc.create_context().0.context_id,
+        server_context_id + 2
+    );
+}
+
+#[tokio::test]
+async fn test_resume_ended_contexts_bounded() {
+    let options = AcceptOptions::default().with_token_store(Arc::new(MemoryTokenStore::default()));
+
+    let (a, b) = mock_utp_pairs();
+    let server_options = options.clone();
+    tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+    let client_conn = connect(a.into(), "").await.unwrap();
+
+    // contexts the client opened before the connection dropped
+    for _ in 0..5 {
+        client_conn.pmc.create_context();
+    }
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &options).await });
+    let resumed_client = resume(a.into(), "", client_conn.resume_token())
+        .await
+        .unwrap();
+    let resumed_server = server.await.unwrap().unwrap();
+
+    for _ in 0..10 {
+        let (tx, _rx) = resumed_client.pmc.create_context();
+        tx.write(Payload::Ok).await.unwrap();
+        tx.close().await.unwrap();
+
+        let (server_tx, server_rx) = resumed_server.pmc.next_context().await.unwrap();
+        assert!(matches!(server_rx.read().await.unwrap(), Payload::Ok));
+        assert!(matches!(
+            server_rx.read().await,
+            Err(ConnectionError::ContextEnded)
+        ));
+        drop(server_tx);
+    }
+
+    assert_eq!(resumed_server.pmc.frame().ended_peer_contexts(), 0);
+}
+
+#[tokio::test]
+async fn test_resume_across_servers() {
+    let store = |key: &str| {
+        AcceptOptions::default()
+            .with_token_store(Arc::new(HmacTokenStore::new(key, Duration::from_secs(60))))
+    };
+    let issuer = store("fleet key");
+    let peer = store("fleet key");
+    let stranger = store("other key");
+
+    let (a, b) = mock_utp_pairs();
+    tokio::spawn(async move { accept_with(b.into(), &issuer).await });
+    let conn = connect(a.into(), "").await.unwrap();
+
+    let (a, b) = mock_utp_pairs();
+    tokio::spawn(async move { accept_with(b.into(), &peer).await });
+    let resumed = resume(a.into(), "", conn.resume_token()).await.unwrap();
+    assert_eq!(resumed.connection_token(), conn.connection_token());
+
+    let (a, b) = mock_utp_pairs();
+    tokio::spawn(async move { accept_with(b.into(), &stranger).await });
+    assert!(resume(a.into(), "", conn.resume_token()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_extensions_agreed() {
+    let server_options = AcceptOptions::default()
+        .with_extensions(Extensions::new().with_compression(&["gzip", "zstd"]));
+    let client_options =
+        ConnectOptions::default().with_extensions(Extensions::new().with_compression(&["zstd"]));
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    let client_conn = connect_with(a.into(), "", &client_options).await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    assert_eq!(client_conn.extensions().compression(), Some("zstd"));
+    assert_eq!(client_conn.extensions(), server_conn.extensions());
+}
+
+struct StaticAuthenticator;
+
+#[async_trait]
+impl Authenticator for StaticAuthenticator {
+    async fn authenticate(&self, hello: &ClientHello, _: &TransportIdentity) -> AuthDecision {
+        if hello.credentials.as_deref() == Some(b"secret") {
+            AuthDecision::Accept(Principal::new(hello.hostname.clone()))
+        } else {
+            AuthDecision::Reject("Invalid credentials.".into())
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_authenticator() {
+    let server_options = AcceptOptions::default().with_authenticator(Arc::new(StaticAuthenticator));
+
+    let (a, b) = mock_utp_pairs();
+    let options = server_options.clone();
+    let server = tokio::spawn(async move { accept_with(b.into(), &options).await });
+
+    let client_options = ConnectOptions::default().with_credentials(&b"secret"[..]);
+    let client_conn = connect_with(a.into(), "zako", &client_options)
+        .await
+        .unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    assert!(client_conn.principal().is_none());
+    assert_eq!(server_conn.principal(), Some(&Principal::new("zako")));
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    assert!(connect(a.into(), "zako").await.is_err());
+    assert!(server.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_resume_other_principal() {
+    let server_options = AcceptOptions::default()
+        .with_authenticator(Arc::new(StaticAuthenticator))
+        .with_token_store(Arc::new(MemoryTokenStore::default()));
+    let client_options = ConnectOptions::default().with_credentials(&b"secret"[..]);
+
+    let (a, b) = mock_utp_pairs();
+    let options = server_options.clone();
+    tokio::spawn(async move { accept_with(b.into(), &options).await });
+    let conn = connect_with(a.into(), "zako", &client_options)
+        .await
+        .unwrap();
+
+    let resume_as = |hostname: &'static str| {
+        let (a, b) = mock_utp_pairs();
+        let options = server_options.clone();
+        tokio::spawn(async move { accept_with(b.into(), &options).await });
+
+        let client_options = client_options
+            .clone()
+            .with_resume_token(conn.resume_token());
+        async move { connect_with(a.into(), hostname, &client_options).await }
+    };
+
+    assert!(resume_as("other").await.is_err());
+    let resumed = resume_as("zako").await.unwrap();
+    assert_eq!(resumed.connection_token(), conn.connection_token());
+}
+
+const HMAC_MECHANISM: &str = "hmac-sha256";
+
+fn hmac_tag(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+struct NonceAuthenticator;
+
+#[async_trait]
+impl Authenticator for NonceAuthenticator {
+    async fn authenticate(&self, _: &ClientHello, _: &TransportIdentity) -> AuthDecision {
+        AuthDecision::Challenge(AuthChallenge {
+            mechanisms: vec![HMAC_MECHANISM.into()],
+            data: b"server-nonce".to_vec(),
+        })
+    }
+
+    async fn verify(
+        &self,
+        hello: &ClientHello,
+        _: &TransportIdentity,
+        challenge: &AuthChallenge,
+        response: &AuthResponse,
+    ) -> AuthDecision {
+        if response.mechanism == HMAC_MECHANISM
+            && response.data == hmac_tag(b"secret", &challenge.data)
+        {
+            AuthDecision::Accept(Principal::new(hello.hostname.clone()))
+        } else {
+            AuthDecision::Reject("Invalid challenge response.".into())
+        }
+    }
+}
+
+struct HmacCredentials(&'static [u8]);
+
+#[async_trait]
+impl CredentialProvider for HmacCredentials {
+    async fn respond(&self, challenge: &AuthChallenge) -> Option<AuthResponse> {
+        if !challenge.mechanisms.iter().any(|m| m == HMAC_MECHANISM) {
+            return None;
+        }
+
+        Some(AuthResponse {
+            mechanism: HMAC_MECHANISM.into(),
+            data: hmac_tag(self.0, &challenge.data),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_challenge_response() {
+    let server_options = AcceptOptions::default().with_authenticator(Arc::new(NonceAuthenticator));
+
+    let (a, b) = mock_utp_pairs();
+    let options = server_options.clone();
+    let server = tokio::spawn(async move { accept_with(b.into(), &options).await });
+
+    let client_options =
+        ConnectOptions::default().with_credential_provider(Arc::new(HmacCredentials(b"secret")));
+    connect_with(a.into(), "zako", &client_options)
+        .await
+        .unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    assert_eq!(server_conn.principal(), Some(&Principal::new("zako")));
+
+    let (a, b) = mock_utp_pairs();
+    let options = server_options.clone();
+    let server = tokio::spawn(async move { accept_with(b.into(), &options).await });
+
+    let client_options =
+        ConnectOptions::default().with_credential_provider(Arc::new(HmacCredentials(b"wrong")));
+    assert!(
+        connect_with(a.into(), "zako", &client_options)
+            .await
+            .is_err()
+    );
+    assert!(server.await.unwrap().is_err());
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    assert!(connect(a.into(), "zako").await.is_err());
+    assert!(server.await.unwrap().is_err());
+}
+
+struct EndlessAuthenticator;
+
+#[async_trait]
+impl Authenticator for EndlessAuthenticator {
+    async fn authenticate(&self, _: &ClientHello, _: &TransportIdentity) -> AuthDecision {
+        AuthDecision::Challenge(AuthChallenge {
+            mechanisms: vec![HMAC_MECHANISM.into()],
+            data: b"server-nonce".to_vec(),
+        })
+    }
+
+    async fn verify(
+        &self,
+        hello: &ClientHello,
+        identity: &TransportIdentity,
+        _: &AuthChallenge,
+        _: &AuthResponse,
+    ) -> AuthDecision {
+        self.authenticate(hello, identity).await
+    }
+}
+
+#[tokio::test]
+async fn test_challenge_rounds_capped() {
+    let server_options =
+        AcceptOptions::default().with_authenticator(Arc::new(EndlessAuthenticator));
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    let client_options =
+        ConnectOptions::default().with_credential_provider(Arc::new(HmacCredentials(b"secret")));
+    let result = connect_with(a.into(), "zako", &client_options).await;
+
+    assert!(matches!(
+        result,
+        Err(ProtofishError::Connection(
+            ConnectionError::HandshakeReject(_)
+        ))
+    ));
+    assert!(matches!(
+        server.await.unwrap(),
+        Err(ProtofishError::Connection(
+            ConnectionError::HandshakeReject(_)
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_keepalive() {
+    let keepalive = KeepaliveOptions::default().with_interval(Duration::from_millis(10));
+    let server_options = AcceptOptions::default().with_keepalive(keepalive.clone());
+    let client_options = ConnectOptions::default().with_keepalive(keepalive);
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept_with(b.into(), &server_options).await });
+
+    let client_conn = connect_with(a.into(), "", &client_options).await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(client_conn.rtt().is_some());
+    assert!(server_conn.rtt().is_some());
+}
+
+#[tokio::test]
+async fn test_graceful_close() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"hi")).await.unwrap();
+    let server_arb = server_conn.next_arb().await.unwrap();
+    assert_eq!(server_arb.read().await.unwrap(), "hi");
+
+    // contexts still held do not hold up the close, and closing again is
+    // a no-op
+    arb.write(Bytes::from_static(b"last")).await.unwrap();
+    client_conn.close("bye").await.unwrap();
+    client_conn.close("again").await.unwrap();
+
+    assert_eq!(server_conn.closed().await.as_deref(), Some("bye"));
+    assert!(server_conn.next_arb().await.is_err());
+
+    // once the stream ended, every operation reports why
+    assert_eq!(server_arb.read().await.unwrap(), "last");
+    assert!(matches!(
+        server_arb.read().await,
+        Err(ArbError::Connection(ConnectionError::PeerClosed(Some(reason)))) if reason == "bye"
+    ));
+    assert!(matches!(
+        server_arb.write(Bytes::from_static(b"late")).await,
+        Err(ArbError::Connection(ConnectionError::PeerClosed(_)))
+    ));
+    assert!(matches!(
+        server_conn.next_arb().await,
+        Err(ConnectionError::PeerClosed(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_benchmark() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move {
+        let options = AcceptOptions::default().with_benchmark_responder();
+        accept_with(b.into(), &options).await.unwrap()
+    });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    // runs are answered without the server waiting in next_arb
+    let _server_conn = server.await.unwrap();
+
+    let result = client_conn
+        .run_benchmark(IntegrityType::Reliable, 100_000)
+        .await
+        .unwrap();
+
+    assert_eq!(result.bytes_sent, 100_000);
+    assert_eq!(result.bytes_received, 100_000);
+    assert_eq!(result.bytes_lost(), 0);
+    assert!(result.throughput() > 0.0);
+
+    let result = client_conn
+        .run_benchmark(IntegrityType::Unreliable, 100_000)
+        .await
+        .unwrap();
+
+    assert!(matches!(result.integrity_type, IntegrityType::Unreliable));
+    assert_eq!(result.bytes_sent, 100_000);
+    assert_eq!(result.bytes_received + result.bytes_lost(), 100_000);
+    assert!(result.bytes_received > 0);
+}
+
+#[tokio::test]
+async fn test_stream_close() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    let (mut writer, _reader) = arb
+        .new_stream(IntegrityType::Reliable)
+        .await
+        .unwrap()
+        .split();
+    writer.write_all(b"data").await.unwrap();
+    writer.shutdown().await.unwrap();
+
+    let (_, rx) = server_conn.pmc.next_context().await.unwrap();
+    let Payload::StreamOpen(open) = rx.read().await.unwrap() else {
+        panic!("expected StreamOpen");
+    };
+    let Payload::StreamClose(close) = rx.read().await.unwrap() else {
+        panic!("expected StreamClose");
+    };
+    assert_eq!(open.stream_id, close.stream_id);
+
+    // StreamClose is skipped when reading arbitrary data
+    let arb = client_conn.new_arb();
+    drop(arb.new_stream(IntegrityType::Reliable).await.unwrap());
+    arb.write(Bytes::from_static(b"after")).await.unwrap();
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    server_arb.wait_stream().await.unwrap();
+    assert_eq!(server_arb.read().await.unwrap(), "after");
+}
+
+#[tokio::test]
+async fn test_remote_error() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"lookup")).await.unwrap();
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    server_arb.read().await.unwrap();
+    server_arb
+        .fail(ErrorType::NotFound, "no such key")
+        .await
+        .unwrap();
+    server_arb
+        .fail(ErrorType::Application(42), "quota exceeded")
+        .await
+        .unwrap();
+
+    let Err(ArbError::Remote { kind, message }) = arb.read().await else {
+        panic!("expected a remote error");
+    };
+    assert_eq!(kind, ErrorType::NotFound);
+    assert_eq!(message, "no such key");
+
+    let Err(ArbError::Remote { kind, .. }) = arb.read().await else {
+        panic!("expected a remote error");
+    };
+    assert_eq!(kind, ErrorType::Application(42));
+}
+
+#[tokio::test]
+async fn test_max_frame_size_agreed() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_options =
+        ConnectOptions::default().with_frame(FrameOptions::default().with_max_frame_size(1024));
+    let _client_conn = connect_with(a.into(), "", &client_options).await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    assert_eq!(server_conn.extensions().max_frame_size(), Some(1024));
+
+    // the server keeps to the smaller limit of the client
+    let result = server_conn
+        .new_arb()
+        .write(Bytes::from(vec![0; 2048]))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ArbError::Connection(ConnectionError::FrameTooLarge {
+            max: 1024,
+            ..
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_max_frame_size_not_raised() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    // an advertised limit above the configured one does not raise it
+    let client_options = ConnectOptions::default()
+        .with_frame(FrameOptions::default().with_max_frame_size(1024))
+        .with_extensions(Extensions::new().with_max_frame_size(4096));
+    let client_conn = connect_with(a.into(), "", &client_options).await.unwrap();
+    let _server_conn = server.await.unwrap().unwrap();
+
+    assert_eq!(client_conn.extensions().max_frame_size(), Some(4096));
+
+    let result = client_conn
+        .new_arb()
+        .write(Bytes::from(vec![0; 2048]))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ArbError::Connection(ConnectionError::FrameTooLarge {
+            max: 1024,
+            ..
+        }))
+    ));
+}
+
+#[cfg(feature = "codec-json")]
+#[tokio::test]
+async fn test_typed_context() {
+    use crate::core::common::codec::{CodecError, JsonCodec};
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let request = client_conn.new_typed::<String, usize, JsonCodec>();
+    request.write(&"protofish".to_string()).await.unwrap();
+
+    let response = server_conn
+        .next_typed::<usize, String, JsonCodec>()
+        .await
+        .unwrap();
+    let name = response.read().await.unwrap();
+    response.write(&name.len()).await.unwrap();
+    assert_eq!(request.read().await.unwrap(), 9);
+
+    // bytes that are not a message of the expected type
+    response
+        .arb()
+        .write(Bytes::from_static(b"\"nine\""))
+        .await
+        .unwrap();
+    assert!(matches!(
+        request.read().await,
+        Err(ArbError::Codec(CodecError::Decode(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_next_event() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    // metadata followed by a stream
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"meta")).await.unwrap();
+    let (mut writer, _reader) = arb
+        .new_stream(IntegrityType::Reliable)
+        .await
+        .unwrap()
+        .split();
+    writer.shutdown().await.unwrap();
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    assert_eq!(server_arb.peek().await.unwrap(), ArbEventKind::Data);
+    assert_eq!(server_arb.peek().await.unwrap(), ArbEventKind::Data);
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::Data(data) if data == "meta"
+    ));
+
+    // a mismatched read keeps the stream for the next call
+    assert!(server_arb.read().await.is_err());
+    assert_eq!(server_arb.peek().await.unwrap(), ArbEventKind::StreamOpened);
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::StreamOpened(_)
+    ));
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::StreamClosed(_)
+    ));
+
+    arb.fail(ErrorType::Cancelled, "gave up").await.unwrap();
+    arb.close().await.unwrap();
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::RemoteError { kind: ErrorType::Cancelled, message } if message == "gave up"
+    ));
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::End
+    ));
+    assert_eq!(server_arb.peek().await.unwrap(), ArbEventKind::End);
+}
+
+#[tokio::test]
+async fn test_peek_concurrent() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    for i in 0..100u8 {
+        arb.write(Bytes::from(vec![i])).await.unwrap();
+    }
+    arb.close().await.unwrap();
+
+    // peeking from another task neither loses nor reorders payloads
+    let (_sender, receiver) = server_conn.next_arb().await.unwrap().split();
+    let receiver = Arc::new(receiver);
+    let peeker = receiver.clone();
+    let peeks = tokio::spawn(async move {
+        while peeker.peek().await.unwrap() != ArbEventKind::End {
+            tokio::task::yield_now().await;
+        }
+    });
+
+    for i in 0..100u8 {
+        assert_eq!(receiver.read().await.unwrap(), Bytes::from(vec![i]));
+    }
+    assert!(matches!(
+        receiver.next_event().await.unwrap(),
+        ArbEvent::End
+    ));
+    peeks.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_into_parts_after_peek() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"meta")).await.unwrap();
+
+    // the peeked payload is the first one of the reader
+    let server_arb = server_conn.next_arb().await.unwrap();
+    assert_eq!(server_arb.peek().await.unwrap(), ArbEventKind::Data);
+    let (_writer, reader) = server_arb.into_parts();
+    assert!(matches!(
+        reader.read().await.unwrap(),
+        Payload::ArbitaryData(data) if data.content == b"meta"
+    ));
+}
+
+#[tokio::test]
+async fn test_arb_split() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let (sender, receiver) = client_conn.new_arb().split();
+    sender.write(Bytes::from_static(b"ping")).await.unwrap();
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    assert_eq!(server_arb.read().await.unwrap(), "ping");
+
+    // the server answers while the client keeps writing from another task
+    let (server_sender, server_receiver) = server_arb.split();
+    let echo = tokio::spawn(async move {
+        while let Ok(data) = server_receiver.read().await {
+            server_sender.write(data).await.unwrap();
+        }
+        server_sender.close().await.unwrap();
+    });
+
+    let writer = sender.clone();
+    tokio::spawn(async move {
+        for i in 0..3u8 {
+            writer.write(Bytes::from(vec![i])).await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    for i in 0..3u8 {
+        assert_eq!(receiver.read().await.unwrap(), Bytes::from(vec![i]));
+    }
+
+    // halves of different contexts stay apart
+    let (other_sender, other_receiver) = client_conn.new_arb().split();
+    let Err(error) = ArbContext::reunite(sender, other_receiver) else {
+        panic!("reunited halves of different contexts");
+    };
+    drop(other_sender);
+
+    let arb = ArbContext::reunite(error.0, receiver).unwrap();
+    arb.close().await.unwrap();
+    assert!(matches!(arb.next_event().await.unwrap(), ArbEvent::End));
+    echo.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_arb_senders_dropped() {
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let (sender, receiver) = client_conn.new_arb().split();
+    sender.write(Bytes::from_static(b"ping")).await.unwrap();
+    let server_arb = server_conn.next_arb().await.unwrap();
+    server_arb
+        .new_stream(IntegrityType::Reliable)
+        .await
+        .unwrap();
+
+    // the receiver alone does not keep the context open
+    drop(sender.clone());
+    drop(sender);
+    assert_eq!(server_arb.read().await.unwrap(), "ping");
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::End
+    ));
+
+    // nor can it accept streams for the ended context
+    assert!(matches!(
+        receiver.wait_stream().await,
+        Err(ArbError::Connection(ConnectionError::ClosedStream))
+    ));
+}
+
+#[cfg(feature = "futures")]
+#[tokio::test]
+async fn test_futures_adapters() {
+    use futures_util::{SinkExt, StreamExt, TryStreamExt, stream};
+
+    use crate::schema::ArbitaryData;
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+    let mut incoming = std::pin::pin!(server_conn.incoming());
+
+    // payloads through the context sink, bytes out of the context stream
+    let (mut writer, reader) = client_conn.pmc.create_context();
+    writer
+        .send(Payload::ArbitaryData(ArbitaryData {
+            content: b"ping".to_vec(),
+        }))
+        .await
+        .unwrap();
+
+    let mut arb = incoming.next().await.unwrap().unwrap();
+    assert_eq!(arb.next().await.unwrap().unwrap(), "ping");
+
+    // bytes through the context sink, payloads out of the reader stream
+    let replies = ["a", "b"].map(|reply| Ok(Bytes::from_static(reply.as_bytes())));
+    SinkExt::send_all(&mut arb, &mut stream::iter(replies))
+        .await
+        .unwrap();
+    SinkExt::close(&mut arb).await.unwrap();
+
+    let payloads: Vec<_> = reader.try_collect().await.unwrap();
+    assert_eq!(payloads.len(), 2);
+    assert!(matches!(&payloads[1], Payload::ArbitaryData(data) if data.content == b"b"));
+
+    // the byte stream reports a stream, which stays for wait_stream
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"meta")).await.unwrap();
+    let stream = arb.new_stream(IntegrityType::Reliable).await.unwrap();
+    arb.write(Bytes::from_static(b"data")).await.unwrap();
+
+    let server_arb = incoming.next().await.unwrap().unwrap();
+    let (_sender, mut receiver) = server_arb.split();
+    assert_eq!(receiver.next().await.unwrap().unwrap(), "meta");
+    for _ in 0..2 {
+        assert!(matches!(
+            receiver.next().await,
+            Some(Err(ArbError::UnexpectedData(_)))
+        ));
+    }
+    receiver.wait_stream().await.unwrap();
+    assert_eq!(receiver.next().await.unwrap().unwrap(), "data");
+
+    // incoming ends with the connection
+    drop((writer, arb, stream));
+    client_conn.close("bye").await.unwrap();
+    assert!(incoming.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_protofish_stream_duplex() {
+    use tokio::io::AsyncReadExt;
+
+    use crate::{ProtofishStream, schema::ArbitaryData};
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    let mut stream = arb.new_stream(IntegrityType::Reliable).await.unwrap();
+    assert_eq!(stream.integrity_type(), IntegrityType::Reliable);
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    let mut server_stream = server_arb.wait_stream().await.unwrap();
+    assert_eq!(server_stream.id(), stream.id());
+
+    // the server echoes everything it reads until the client half-closes
+    let echo = tokio::spawn(async move {
+        let mut data = Vec::new();
+        server_stream.read_to_end(&mut data).await.unwrap();
+        server_stream.write_all(&data).await.unwrap();
+        server_stream.shutdown_write().await.unwrap();
+        server_arb
+    });
+
+    stream.write_all(b"duplex").await.unwrap();
+    stream.shutdown_write().await.unwrap();
+
+    let mut echoed = Vec::new();
+    stream.read_to_end(&mut echoed).await.unwrap();
+    assert_eq!(echoed, b"duplex");
+
+    // the stream writes on the context it was opened on
+    let server_arb = echo.await.unwrap();
+    stream
+        .context()
+        .write(Payload::ArbitaryData(ArbitaryData {
+            content: b"meta".to_vec(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(server_arb.read().await.unwrap(), "meta");
+
+    // halves only go back together with their own stream
+    let other = arb.new_stream(IntegrityType::Reliable).await.unwrap();
+    let other_id = other.id();
+    let (writer, reader) = stream.split();
+    let (other_writer, other_reader) = other.split();
+
+    let Err(error) = ProtofishStream::unsplit(writer, other_reader) else {
+        panic!("unsplit halves of different streams");
+    };
+    let stream = ProtofishStream::unsplit(error.0, reader).unwrap();
+    assert_eq!(stream.integrity_type(), IntegrityType::Reliable);
+
+    // the peer's half has the same ID, but belongs to another context
+    let server_stream = server_arb.wait_stream().await.unwrap();
+    assert_eq!(server_stream.id(), other_id);
+    let (_server_writer, server_reader) = server_stream.split();
+    let Err(server_error) = ProtofishStream::unsplit(other_writer, server_reader) else {
+        panic!("unsplit halves of different connections");
+    };
+    assert!(ProtofishStream::unsplit(server_error.0, error.1).is_ok());
+}
+
+#[tokio::test]
+async fn test_unreliable_channel() {
+    use crate::utp::{UnreliableChannel, error::UTPError, tests::stream::MOCK_MAX_MESSAGE_SIZE};
+
+    let (a, b) = mock_utp_pairs();
+    let server = tokio::spawn(async move { accept(b.into()).await });
+
+    let client_conn = connect(a.into(), "").await.unwrap();
+    let server_conn = server.await.unwrap().unwrap();
+
+    let arb = client_conn.new_arb();
+    arb.write(Bytes::from_static(b"meta")).await.unwrap();
+    let channel = arb.new_channel().await.unwrap();
+
+    let server_arb = server_conn.next_arb().await.unwrap();
+    assert!(server_arb.wait_channel().await.is_err());
+    assert_eq!(server_arb.read().await.unwrap(), "meta");
+    let server_channel = server_arb.wait_channel().await.unwrap();
+    assert_eq!(server_channel.id(), channel.id());
+
+    // messages keep their boundaries
+    channel.send(Bytes::from_static(b"one")).await.unwrap();
+    channel.send(Bytes::from_static(b"two")).await.unwrap();
+    assert_eq!(server_channel.recv().await.unwrap(), "one");
+    assert_eq!(server_channel.recv().await.unwrap(), "two");
+
+    let oversized = Bytes::from(vec![0; MOCK_MAX_MESSAGE_SIZE + 1]);
+    assert!(matches!(
+        channel.send(oversized).await,
+        Err(UTPError::MessageTooLarge {
+            max: MOCK_MAX_MESSAGE_SIZE,
+            ..
+        })
+    ));
+
+    // dropping the channel tells the peer
+    drop(channel);
+    assert!(matches!(
+        server_arb.next_event().await.unwrap(),
+        ArbEvent::StreamClosed(_)
+    ));
+    assert!(matches!(
+        server_channel.recv().await,
+        Err(UTPError::ChannelClosed)
+    ));
+}
@@ -1,106 +1,731 @@
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    future::poll_fn,
+    io::ErrorKind,
+    sync::{
+        Arc, OnceLock, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll, ready},
+};
 
 use bytes::Bytes;
 use dashmap::DashMap;
 use tokio::{
-    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     sync::{
-        Mutex, Notify,
-        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        Mutex, Notify, OwnedSemaphorePermit, Semaphore,
+        mpsc::{self, error::TrySendError},
+        watch,
     },
     task::JoinHandle,
 };
 
 use crate::{
-    internal::serialize::{deserialize_message, serialize_message},
-    schema::{ContextId, Message, Payload},
+    constant::{CLIENT_KEEPALIVE_CONTEXT_ID, CONTROL_CONTEXT_ID, SERVER_KEEPALIVE_CONTEXT_ID},
+    core::common::{
+        decode::DecodePolicy,
+        error::ConnectionError,
+        queue::{OverflowPolicy, QueueOptions},
+    },
+    internal::serialize::{MalformedFrame, deserialize_message, serialize_message},
+    schema::{Close, ContextId, DecodeError, Error, ErrorType, Message, Payload, StreamId},
     utp::{UTPStream, error::UTPError},
 };
 
-type SenderMap = Arc<DashMap<ContextId, UnboundedSender<Payload>>>;
+type SenderMap = Arc<DashMap<ContextId, Route>>;
+
+/// Queue of messages written by the writer task, in order.
+#[derive(Clone)]
+struct Outbox {
+    sender: mpsc::UnboundedSender<(Message, InFlight)>,
+    in_flight: Arc<watch::Sender<usize>>,
+}
+
+impl Outbox {
+    fn send(&self, message: Message) {
+        // the writer task outlives every queued message
+        let _ = self.sender.send((message, InFlight::new(&self.in_flight)));
+    }
+}
+
+/// Counts a message as in flight until dropped.
+struct InFlight(Arc<watch::Sender<usize>>);
+
+impl InFlight {
+    fn new(in_flight: &Arc<watch::Sender<usize>>) -> Self {
+        in_flight.send_modify(|n| *n += 1);
+        Self(in_flight.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.send_modify(|n| *n -= 1);
+    }
+}
+
+/// Queue of a subscribed context, as seen by the PMC reader.
+#[derive(Clone)]
+struct Route {
+    sender: mpsc::Sender<Queued>,
+    state: Arc<RouteState>,
+
+    /// Bytes the queue may still take
+    bytes: Arc<Semaphore>,
+}
+
+/// A payload waiting in the queue of a context.
+struct Queued {
+    payload: Payload,
+
+    /// Returns the bytes of the payload to the queue once it is read
+    _bytes: Option<OwnedSemaphorePermit>,
+}
+
+/// State shared between a [`Route`] and its [`Subscription`].
+#[derive(Default)]
+struct RouteState {
+    /// Payloads dropped because the queue was full
+    dropped: AtomicU64,
+
+    /// Set when the peer ended the context while the queue was full
+    ended: AtomicBool,
+
+    /// Set once this side ended the context, shared with its writers
+    local_end: Arc<AtomicBool>,
+
+    /// Why the context failed, if a payload of it could not be decoded
+    malformed: parking_lot::Mutex<Option<DecodeError>>,
+}
+
+/// A context opened by the peer.
+///
+/// The context is subscribed as soon as its first message arrives, so later
+/// messages are queued in `receiver` even before the context is handed out.
+pub struct IncomingContext {
+    pub message: Message,
+    pub receiver: Subscription,
+}
+
+/// Outcome of [`Subscription::recv`].
+pub enum Received {
+    Payload(Payload),
+
+    /// The peer ended the context.
+    Ended,
+
+    /// Payloads were dropped because the queue was full.
+    Overflowed(u64),
+
+    /// A payload could not be decoded, which ended the context.
+    Malformed(DecodeError),
+
+    /// The PMC stopped receiving, for the given reason.
+    Closed(ConnectionError),
+}
+
+/// Receives the payloads of a context.
+///
+/// Dropping it unsubscribes the context; later messages of the context are
+/// dropped.
+pub struct Subscription {
+    context_id: ContextId,
+    receiver: mpsc::Receiver<Queued>,
+    state: Arc<RouteState>,
+    overflow: OverflowPolicy,
+    senders: Weak<DashMap<ContextId, Route>>,
+    control: Arc<Control>,
+}
+
+impl Subscription {
+    fn new(
+        context_id: ContextId,
+        queue: &QueueOptions,
+        senders: &SenderMap,
+        control: &Arc<Control>,
+    ) -> (Route, Self) {
+        let (sender, receiver) = mpsc::channel(queue.capacity.max(1));
+        let state = Arc::new(RouteState::default());
+
+        let route = Route {
+            sender,
+            state: state.clone(),
+            bytes: Arc::new(Semaphore::new(queue.byte_limit() as usize)),
+        };
+        let subscription = Self {
+            context_id,
+            receiver,
+            state,
+            overflow: queue.overflow,
+            senders: Arc::downgrade(senders),
+            control: control.clone(),
+        };
+
+        (route, subscription)
+    }
+
+    pub async fn recv(&mut self) -> Received {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Received> {
+        // a rejected payload is reported right away, the end of an overflowed
+        // context after the payloads queued before
+        if self.overflow == OverflowPolicy::Reject {
+            let dropped = self.state.dropped.swap(0, Ordering::AcqRel);
+            if dropped > 0 {
+                return Poll::Ready(Received::Overflowed(dropped));
+            }
+        }
+
+        Poll::Ready(
+            match ready!(self.receiver.poll_recv(cx)).map(|queued| queued.payload) {
+                Some(Payload::Close(_)) => Received::Ended,
+                Some(payload) => Received::Payload(payload),
+                None if self.state.ended.load(Ordering::Acquire) => Received::Ended,
+                None if let Some(error) = self.state.malformed.lock().take() => {
+                    Received::Malformed(error)
+                }
+                None => match self.state.dropped.swap(0, Ordering::AcqRel) {
+                    0 => Received::Closed(self.control.closed_error()),
+                    dropped => Received::Overflowed(dropped),
+                },
+            },
+        )
+    }
+
+    pub fn try_recv(&mut self) -> Option<Payload> {
+        self.receiver.try_recv().ok().map(|queued| queued.payload)
+    }
+
+    /// Returns the flag that marks the context as ended by this side.
+    ///
+    /// The writers of the context share it, so that they stop writing once
+    /// the context is ended for overflowing.
+    pub fn local_end(&self) -> Arc<AtomicBool> {
+        self.state.local_end.clone()
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(senders) = self.senders.upgrade() {
+            senders.remove(&self.context_id);
+        }
+        self.control.end_peer_context(self.context_id);
+    }
+}
+
+type StreamCloseHook = Box<dyn Fn(StreamId) + Send + Sync>;
+
+type IncomingHook = Box<dyn Fn(IncomingContext) -> Option<IncomingContext> + Send + Sync>;
+
+/// Why the PMC stopped receiving.
+#[derive(Debug, Clone)]
+pub enum Termination {
+    /// Stopped locally, e.g. by closing the connection
+    Local,
+
+    /// The peer stopped answering keepalives
+    Unresponsive,
+
+    /// The stream ended, with the reason of the peer's `Close` if it sent one
+    PeerClosed(Option<String>),
+
+    /// The peer ended the connection with an `Error` on the control context
+    PeerError(Error),
+
+    /// The peer sent a frame this side cannot accept
+    ProtocolViolation(String),
+
+    /// A frame could not be decoded under [`DecodePolicy::Fatal`]
+    Decode(DecodeError),
+
+    /// The stream failed
+    Transport(Arc<UTPError>),
+}
+
+impl From<&Termination> for ConnectionError {
+    fn from(termination: &Termination) -> Self {
+        match termination {
+            Termination::Local => ConnectionError::ClosedStream,
+            Termination::Unresponsive => ConnectionError::PeerUnresponsive,
+            Termination::PeerClosed(reason) => ConnectionError::PeerClosed(reason.clone()),
+            Termination::PeerError(error) => ConnectionError::PeerError {
+                kind: error.error_type,
+                message: error.message.clone(),
+            },
+            Termination::ProtocolViolation(message) => {
+                ConnectionError::ProtocolViolation(message.clone())
+            }
+            Termination::Decode(error) => ConnectionError::Decode(error.clone()),
+            Termination::Transport(error) => ConnectionError::Transport(error.clone()),
+        }
+    }
+}
+
+/// Context IDs of the peer that ended.
+///
+/// The peer hands out IDs in order but may use them in any order, so ended IDs
+/// are kept explicitly above `floor`, the lowest ID not known to have ended.
+/// IDs are compared relative to `floor`, which keeps working once the peer's
+/// counter wraps.
+struct EndedContexts {
+    floor: ContextId,
+    ids: HashSet<ContextId>,
+
+    /// Set once the peer resumed a session; its IDs then continue from an
+    /// earlier connection, and the floor moves to the next new one.
+    reseed: bool,
+}
+
+impl EndedContexts {
+    fn new(parity: ContextId) -> Self {
+        Self {
+            floor: parity,
+            ids: HashSet::new(),
+            reseed: false,
+        }
+    }
+
+    /// Returns `true` if `context_id` did not end, moving the floor to it if
+    /// it is the first context since the peer resumed.
+    fn open(&mut self, context_id: ContextId) -> bool {
+        if self.contains(context_id) {
+            return false;
+        }
+
+        if self.reseed {
+            self.reseed = false;
+            self.floor = context_id;
+            self.ids.clear();
+        }
+
+        true
+    }
+
+    fn contains(&self, context_id: ContextId) -> bool {
+        // below the floor, allowing for the floor to have wrapped
+        (self.floor.wrapping_sub(context_id) as i64) > 0 || self.ids.contains(&context_id)
+    }
+
+    fn insert(&mut self, context_id: ContextId) {
+        if self.contains(context_id) {
+            return;
+        }
+
+        self.ids.insert(context_id);
+        while self.ids.remove(&self.floor) {
+            self.floor += 2;
+            // the counter skips the reserved IDs and restarts, see `ContextCounter`
+            if self.floor >= CONTROL_CONTEXT_ID {
+                self.floor = if self.floor.is_multiple_of(2) { 2 } else { 1 };
+            }
+        }
+    }
+}
+
+/// Connection-level state driven by messages on the control context.
+struct Control {
+    /// Forwards new contexts; taken once new contexts are no longer accepted.
+    context_tx: parking_lot::Mutex<Option<mpsc::Sender<IncomingContext>>>,
+
+    /// How the peer announced the end of the connection, if it did
+    peer_cause: parking_lot::Mutex<Option<Termination>>,
+
+    /// Set once, when the PMC stopped receiving
+    terminal: watch::Sender<Option<Termination>>,
+
+    /// Set once the peer closed the connection or frames stopped arriving
+    ended: watch::Sender<bool>,
+
+    /// Called with the ID of every stream the peer closes
+    on_stream_close: OnceLock<StreamCloseHook>,
+
+    /// Sees every context the peer opens before it is handed out
+    on_incoming: OnceLock<IncomingHook>,
+
+    /// Parity of the context IDs the peer opens
+    peer_parity: ContextId,
+
+    /// Contexts the peer opened that ended since
+    peer_ended: parking_lot::Mutex<EndedContexts>,
+
+    /// Largest frame sent or received, in bytes
+    max_frame_size: AtomicU64,
+
+    /// Set while the reader waits for a full queue under
+    /// [`OverflowPolicy::Block`]
+    stalled: AtomicBool,
+
+    /// Number of payloads of unknown types and undecodable frames that were
+    /// skipped
+    skipped_payloads: AtomicU64,
+
+    /// What happens to frames that cannot be decoded
+    decode_policy: DecodePolicy,
+}
+
+impl Control {
+    /// Returns `true` if `context_id` is a context the peer opens just now,
+    /// rather than a late message of an ended one.
+    ///
+    /// Only called for contexts without a route, so the peer's live contexts
+    /// are never mistaken for new ones.
+    fn is_new_peer_context(&self, context_id: ContextId) -> bool {
+        context_id % 2 == self.peer_parity && self.peer_ended.lock().open(context_id)
+    }
+
+    /// Remembers that a context of the peer ended, so its late messages do not
+    /// open it again.
+    fn end_peer_context(&self, context_id: ContextId) {
+        if context_id % 2 == self.peer_parity {
+            self.peer_ended.lock().insert(context_id);
+        }
+    }
+
+    /// Marks the reader as stalled while it waits for `future`.
+    async fn stall<F: Future>(&self, future: F) -> F::Output {
+        self.stalled.store(true, Ordering::Release);
+        let output = future.await;
+        self.stalled.store(false, Ordering::Release);
+
+        output
+    }
+
+    /// Returns the sender of new contexts, unless they are no longer accepted.
+    fn context_tx(&self) -> Option<mpsc::Sender<IncomingContext>> {
+        self.context_tx.lock().clone()
+    }
+
+    fn max_frame_size(&self) -> u64 {
+        self.max_frame_size.load(Ordering::Acquire)
+    }
+
+    fn stop_accepting(&self) {
+        self.context_tx.lock().take();
+    }
+
+    /// Records why the PMC stopped receiving, unless a cause is known already.
+    fn terminate(&self, cause: Termination) {
+        self.terminal.send_if_modified(|terminal| {
+            if terminal.is_some() {
+                return false;
+            }
+
+            terminal.replace(cause);
+            true
+        });
+    }
+
+    /// Returns why the PMC stopped receiving, if it did.
+    fn terminal_error(&self) -> Option<ConnectionError> {
+        self.terminal.borrow().as_ref().map(ConnectionError::from)
+    }
+
+    /// Returns the error of reads after the PMC stopped receiving.
+    fn closed_error(&self) -> ConnectionError {
+        self.terminal_error()
+            .unwrap_or(ConnectionError::ClosedStream)
+    }
+
+    /// Returns the cause of a stream that ended without an error.
+    fn peer_closed(&self) -> Termination {
+        self.peer_cause
+            .lock()
+            .clone()
+            .unwrap_or(Termination::PeerClosed(None))
+    }
+
+    fn end(&self) {
+        self.stop_accepting();
+        self.ended.send_replace(true);
+    }
+}
 
 pub struct PMCFrame<U>
 where
     U: UTPStream,
 {
     senders: SenderMap,
-    context_rx: Mutex<UnboundedReceiver<Message>>,
-    writer: Mutex<U::StreamWrite>,
+    queue: QueueOptions,
+    context_rx: Mutex<mpsc::Receiver<IncomingContext>>,
+    writer: Arc<Mutex<U::StreamWrite>>,
+    outbox: Outbox,
+    control: Arc<Control>,
+    in_flight: Arc<watch::Sender<usize>>,
     shutdown_notify: Arc<Notify>,
     _task: JoinHandle<()>,
+    _writer_task: JoinHandle<()>,
 }
 
 impl<U> PMCFrame<U>
 where
     U: UTPStream,
 {
-    pub fn new(stream: U) -> Self {
+    pub fn new(
+        is_server: bool,
+        stream: U,
+        queue: QueueOptions,
+        max_frame_size: u64,
+        decode_policy: DecodePolicy,
+    ) -> Self {
         let senders: SenderMap = Default::default();
-        let (context_tx, context_rx) = mpsc::unbounded_channel();
+        let (context_tx, context_rx) = mpsc::channel(queue.backlog.max(1));
         let shutdown_notify = Arc::new(Notify::new());
+        let control = Arc::new(Control {
+            context_tx: Some(context_tx).into(),
+            peer_cause: None.into(),
+            terminal: watch::Sender::new(None),
+            ended: watch::Sender::new(false),
+            on_stream_close: OnceLock::new(),
+            on_incoming: OnceLock::new(),
+            // clients open even contexts, servers odd ones
+            peer_parity: if is_server { 0 } else { 1 },
+            peer_ended: EndedContexts::new(if is_server { 0 } else { 1 }).into(),
+            max_frame_size: max_frame_size.into(),
+            stalled: AtomicBool::new(false),
+            skipped_payloads: AtomicU64::new(0),
+            decode_policy,
+        });
 
         let (writer, mut reader) = stream.split();
+        let writer = Arc::new(Mutex::new(writer));
+        let in_flight = Arc::new(watch::Sender::new(0));
+        let (sender, mut outbox_rx) = mpsc::unbounded_channel();
+        let outbox = Outbox {
+            sender,
+            in_flight: in_flight.clone(),
+        };
+
+        // writes queued messages, so that the reader never waits on a write
+        let _writer_task = {
+            let writer = writer.clone();
+
+            tokio::spawn(async move {
+                while let Some((message, _in_flight)) = outbox_rx.recv().await {
+                    if let Err(e) = write_frame(&writer, message).await {
+                        tracing::debug!("Failed to write queued frame: {}", e);
+                    }
+                }
+            })
+        };
 
         let _task = {
             let senders = senders.clone();
             let notify = shutdown_notify.clone();
+            let writer = writer.clone();
+            let outbox = outbox.clone();
+            let control = control.clone();
+            let queue = queue.clone();
 
             tokio::spawn(async move {
-                loop {
+                let cause = loop {
                     tokio::select! {
                         _ = notify.notified() => {
-                            break;
+                            break Termination::Local;
                         }
-                        success = match_frame(&mut reader, senders.clone(), context_tx.clone()) => {
-                            if !success {break;}
+                        result = match_frame(
+                            &mut reader, &writer, &outbox, &senders, &control, &queue,
+                        ) => {
+                            if let Err(cause) = result {
+                                break cause;
+                            }
                         }
                     }
-                }
+                };
+
+                // fail all open context readers with the cause
+                control.terminate(cause);
+                senders.clear();
+                control.end();
             })
         };
 
         Self {
             senders,
+            queue,
             context_rx: Mutex::new(context_rx),
             shutdown_notify,
-            writer: Mutex::new(writer),
+            writer,
+            outbox,
+            control,
+            in_flight,
             _task,
+            _writer_task,
         }
     }
 
-    pub fn subscribe_context(
+    /// Lowers the largest frame size, e.g. to the one agreed on in the
+    /// handshake. The configured limit is never raised.
+    pub fn lower_max_frame_size(&self, max_frame_size: u64) {
+        self.control
+            .max_frame_size
+            .fetch_min(max_frame_size, Ordering::AcqRel);
+    }
+
+    /// Returns whether the reader waits for a full queue, so that frames of
+    /// other contexts, such as keepalive echoes, are not read meanwhile.
+    pub fn is_stalled(&self) -> bool {
+        self.control.stalled.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of payloads of unknown types and undecodable frames
+    /// that were skipped.
+    pub fn skipped_payloads(&self) -> u64 {
+        self.control.skipped_payloads.load(Ordering::Relaxed)
+    }
+
+    /// Sets the function called when the peer closes a stream.
+    pub fn on_stream_close(&self, hook: impl Fn(StreamId) + Send + Sync + 'static) {
+        if self.control.on_stream_close.set(Box::new(hook)).is_err() {
+            tracing::warn!("Stream close hook is already set");
+        }
+    }
+
+    /// Sets the function that sees every context opened by the peer before
+    /// it is handed out by [`PMCFrame::next_context`].
+    ///
+    /// The function runs on the PMC reader and must not block. Returning
+    /// `None` takes the context over.
+    pub fn on_incoming(
         &self,
-        context_id: ContextId,
-        initial_item: Option<Payload>,
-    ) -> UnboundedReceiver<Payload> {
-        let (tx, rx) = mpsc::unbounded_channel();
+        hook: impl Fn(IncomingContext) -> Option<IncomingContext> + Send + Sync + 'static,
+    ) {
+        if self.control.on_incoming.set(Box::new(hook)).is_err() {
+            tracing::warn!("Incoming context hook is already set");
+        }
+    }
+
+    /// Stops handing out new contexts of the peer; their messages are dropped.
+    pub fn stop_accepting(&self) {
+        self.control.stop_accepting();
+    }
+
+    /// Waits until the peer closed the connection or frames stopped arriving.
+    ///
+    /// Returns the reason the peer gave in its `Close`, if any.
+    pub async fn closed(&self) -> Option<String> {
+        let mut ended = self.control.ended.subscribe();
+        // the sender lives as long as `self`
+        let _ = ended.wait_for(|ended| *ended).await;
 
-        if let Some(item) = initial_item {
-            send_curried(tx.clone())(item);
+        match self.control.peer_cause.lock().clone() {
+            Some(Termination::PeerClosed(reason)) => reason,
+            Some(Termination::PeerError(error)) => Some(error.message),
+            _ => None,
         }
+    }
+
+    /// Waits until the PMC stopped receiving, returning why.
+    pub async fn terminated(&self) -> ConnectionError {
+        let mut terminal = self.control.terminal.subscribe();
+        // the sender lives as long as `self`
+        let _ = terminal.wait_for(Option::is_some).await;
+
+        self.control.closed_error()
+    }
+
+    /// Closes the writing half of the stream, so the peer reads its end.
+    pub async fn shutdown_write(&self) -> Result<(), UTPError> {
+        self.writer.lock().await.shutdown().await?;
+
+        Ok(())
+    }
+
+    /// Waits until every message being sent or queued so far is written.
+    pub async fn drained(&self) {
+        let mut in_flight = self.in_flight.subscribe();
+        // the sender lives as long as `self`
+        let _ = in_flight.wait_for(|n| *n == 0).await;
+    }
+
+    /// Stops receiving frames, failing all open context readers and
+    /// [`PMCFrame::next_context`].
+    pub fn shutdown(&self) {
+        self.terminate(Termination::Local);
+    }
+
+    /// Stops receiving frames, failing all pending and later operations with
+    /// `cause`.
+    pub fn terminate(&self, cause: Termination) {
+        self.control.terminate(cause);
+        self.shutdown_notify.notify_one();
+        self.senders.clear();
+    }
+
+    pub fn subscribe_context(&self, context_id: ContextId) -> Subscription {
+        let (route, subscription) =
+            Subscription::new(context_id, &self.queue, &self.senders, &self.control);
+
+        self.senders.insert(context_id, route);
+
+        subscription
+    }
 
-        self.senders.insert(context_id, tx);
+    /// Returns the number of subscribed contexts.
+    #[cfg(test)]
+    pub fn subscribed(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Returns the number of ended peer contexts kept above the floor.
+    #[cfg(test)]
+    pub fn ended_peer_contexts(&self) -> usize {
+        self.control.peer_ended.lock().ids.len()
+    }
 
-        rx
+    /// Notes that the peer resumed a session, so its context IDs continue
+    /// from an earlier connection rather than from the start.
+    pub fn peer_resumed(&self) {
+        self.control.peer_ended.lock().reseed = true;
     }
 
-    pub async fn next_context_message(&self) -> Option<Message> {
-        self.context_rx.lock().await.recv().await
+    /// Returns the next context opened by the peer, or why no more will
+    /// arrive.
+    pub async fn next_context(&self) -> Result<IncomingContext, ConnectionError> {
+        self.context_rx
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| self.control.closed_error())
     }
 
-    pub async fn send_frame(&self, message: Message) -> Result<(), UTPError> {
+    /// Sends a message, refusing frames larger than the peer accepts.
+    ///
+    /// Fails with the cause once the PMC stopped receiving.
+    pub async fn send_frame(&self, message: Message) -> Result<(), ConnectionError> {
+        if let Some(error) = self.control.terminal_error() {
+            return Err(error);
+        }
+
+        let _in_flight = InFlight::new(&self.in_flight);
         let buf = serialize_message(message);
 
-        let len: u64 = buf.len() as u64;
-        let len_bytes = len.to_le_bytes();
-        let len_bytes = Bytes::copy_from_slice(&len_bytes);
+        let size = buf.len() as u64;
+        let max = self.control.max_frame_size();
+        if size > max {
+            return Err(ConnectionError::FrameTooLarge { size, max });
+        }
 
-        let mut writer = self.writer.lock().await;
-        writer.write_all(&len_bytes).await?;
-        writer.write_all(&buf).await?;
+        if let Err(e) = write_buf(&self.writer, &buf).await {
+            // a failed write usually means the stream ended for a known reason
+            return Err(self.control.terminal_error().unwrap_or(e.into()));
+        }
 
         Ok(())
     }
+
+    /// Queues a message to be written after the messages queued before,
+    /// without waiting for the write.
+    ///
+    /// Unlike [`PMCFrame::send_frame`], this works outside of an async
+    /// context, e.g. in `Drop`.
+    pub fn queue_frame(&self, message: Message) {
+        self.outbox.send(message);
+    }
 }
 
 impl<U: UTPStream> Drop for PMCFrame<U> {
@@ -109,56 +734,485 @@ impl<U: UTPStream> Drop for PMCFrame<U> {
     }
 }
 
-async fn match_frame<R: AsyncRead + Unpin>(
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &Mutex<W>,
+    message: Message,
+) -> Result<(), UTPError> {
+    write_buf(writer, &serialize_message(message)).await
+}
+
+async fn write_buf<W: AsyncWrite + Unpin>(writer: &Mutex<W>, buf: &[u8]) -> Result<(), UTPError> {
+    let len: u64 = buf.len() as u64;
+    let len_bytes = len.to_le_bytes();
+    let len_bytes = Bytes::copy_from_slice(&len_bytes);
+
+    let mut writer = writer.lock().await;
+    writer.write_all(&len_bytes).await?;
+    writer.write_all(buf).await?;
+
+    Ok(())
+}
+
+async fn match_frame<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
     stream: &mut R,
-    senders: SenderMap,
-    context_tx: UnboundedSender<Message>,
-) -> bool {
-    match recv_frame(stream).await {
-        Ok(message_option) => {
-            if let Some(message) = message_option {
-                if let Some(sender) = senders.get(&message.context_id) {
-                    send_curried(sender.clone())(message.payload);
-                } else {
-                    send_curried(context_tx)(message);
-                }
+    writer: &Mutex<W>,
+    outbox: &Outbox,
+    senders: &SenderMap,
+    control: &Arc<Control>,
+    queue: &QueueOptions,
+) -> Result<(), Termination> {
+    match recv_frame(stream, control.max_frame_size()).await {
+        Ok((message, size)) => {
+            route_message(message, size, writer, outbox, senders, control, queue).await;
+            Ok(())
+        }
+        Err(RecvError::Malformed(frame)) => match_malformed(writer, senders, control, frame).await,
+        Err(RecvError::Oversized(size)) => {
+            let max = control.max_frame_size();
+            tracing::error!(
+                "Peer sent a frame of {} bytes, exceeding the maximum of {}",
+                size,
+                max
+            );
 
-                true
-            } else {
-                false
-            }
+            // the stream is out of sync after an unread frame
+            let message = format!("frame of {} bytes exceeds the maximum of {}", size, max);
+            protocol_error(writer, ErrorType::ResourceExhausted, message.clone()).await;
+
+            Err(Termination::ProtocolViolation(message))
+        }
+        Err(RecvError::Transport(UTPError::Warn(e))) => {
+            tracing::warn!("UTP receive warn: {}", e);
+            Ok(())
         }
-        Err(UTPError::Fatal(e)) => {
+        Err(RecvError::Ended) => Err(control.peer_closed()),
+        Err(RecvError::Transport(UTPError::Io(e)))
+            if e.kind() == ErrorKind::UnexpectedEof && control.peer_cause.lock().is_some() =>
+        {
+            // cut off after the peer ended the connection
+            Err(control.peer_closed())
+        }
+        Err(RecvError::Transport(e)) => {
             tracing::error!("UTP receive failure: {}", e);
-            false
+            Err(Termination::Transport(Arc::new(e)))
         }
-        Err(UTPError::Warn(e)) => {
-            tracing::warn!("UTP receive warn: {}", e);
-            true
+    }
+}
+
+/// Hands a message to its context, the control context or a new context.
+async fn route_message<W: AsyncWrite + Unpin>(
+    message: Message,
+    size: usize,
+    writer: &Mutex<W>,
+    outbox: &Outbox,
+    senders: &SenderMap,
+    control: &Arc<Control>,
+    queue: &QueueOptions,
+) {
+    if let Payload::StreamClose(close) = &message.payload
+        && let Some(hook) = control.on_stream_close.get()
+    {
+        hook(close.stream_id);
+    }
+
+    let route = senders.get(&message.context_id).map(|route| route.clone());
+
+    if let Some(route) = route {
+        let context_id = message.context_id;
+        let ended = matches!(message.payload, Payload::Close(_));
+
+        deliver(route, message, size, writer, senders, control, queue).await;
+        if ended {
+            // the reader sees the `Close`, then the end of the channel
+            senders.remove(&context_id);
+            control.end_peer_context(context_id);
         }
-        Err(UTPError::Io(_)) => {
-            // stream closed
-            false
+    } else if is_keepalive_probe(&message) {
+        // echo the peer's keepalive so it can measure the round trip
+        outbox.send(message);
+    } else if message.context_id == CONTROL_CONTEXT_ID {
+        match_control(control, message.payload);
+    } else if !control.is_new_peer_context(message.context_id) {
+        tracing::trace!("Dropping message of ended context {}", message.context_id);
+    } else if matches!(message.payload, Payload::Close(_)) {
+        control.end_peer_context(message.context_id);
+        tracing::trace!(
+            "Ignoring context {} ended before sending anything",
+            message.context_id
+        );
+    } else if let Some(context_tx) = control.context_tx() {
+        let context_id = message.context_id;
+        let (route, receiver) = Subscription::new(context_id, queue, senders, control);
+        senders.insert(context_id, route);
+
+        let incoming = IncomingContext { message, receiver };
+        let incoming = match control.on_incoming.get() {
+            Some(hook) => match hook(incoming) {
+                Some(incoming) => incoming,
+                None => return,
+            },
+            None => incoming,
+        };
+
+        if queue.overflow == OverflowPolicy::Block {
+            let _ = control.stall(context_tx.send(incoming)).await;
+        } else if let Err(TrySendError::Full(incoming)) = context_tx.try_send(incoming) {
+            // unsubscribes the context
+            drop(incoming);
+            refuse(writer, context_id, "too many pending contexts", true).await;
         }
+    } else {
+        tracing::debug!(
+            "Dropping message of context {} opened after close",
+            message.context_id
+        );
     }
 }
 
-async fn recv_frame<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Option<Message>, UTPError> {
-    let len = stream.read_u64_le().await?;
+/// Handles a frame that could not be decoded, returning whether to keep
+/// receiving.
+///
+/// Payloads unknown to this version are skipped for forward compatibility.
+/// Other failures are handled according to the [`DecodePolicy`]: a skipped
+/// malformed payload fails its context, while a fatal failure ends the
+/// connection and becomes the error of all pending reads.
+async fn match_malformed<W: AsyncWrite + Unpin>(
+    writer: &Mutex<W>,
+    senders: &SenderMap,
+    control: &Control,
+    frame: MalformedFrame,
+) -> Result<(), Termination> {
+    match frame {
+        MalformedFrame {
+            context_id: Some(context_id),
+            error: DecodeError::UnknownPayload,
+        } => {
+            tracing::debug!("Skipping unknown payload on context {}", context_id);
+            control.skipped_payloads.fetch_add(1, Ordering::Relaxed);
+
+            Ok(())
+        }
+        MalformedFrame { context_id, error } if control.decode_policy == DecodePolicy::Fatal => {
+            tracing::error!("Undecodable frame on context {:?}: {}", context_id, error);
+            protocol_error(writer, ErrorType::Malformed, error.to_string()).await;
+
+            Err(Termination::Decode(error))
+        }
+        MalformedFrame {
+            context_id: Some(context_id),
+            error,
+        } => {
+            tracing::warn!("Malformed payload on context {}: {}", context_id, error);
+            fail_context(writer, senders, control, context_id, error).await;
+
+            Ok(())
+        }
+        MalformedFrame {
+            context_id: None,
+            error,
+        } => {
+            // Without a context, the frame could only be reported on the
+            // control context, where an `Error` ends the connection.
+            tracing::warn!("Skipping undecodable frame: {}", error);
+            control.skipped_payloads.fetch_add(1, Ordering::Relaxed);
+
+            Ok(())
+        }
+    }
+}
+
+/// Fails a context with a malformed payload, reporting it to the peer.
+///
+/// The peer is told once per context; later frames of the context are
+/// dropped like those of any ended context, so they are not answered one by
+/// one.
+async fn fail_context<W: AsyncWrite + Unpin>(
+    writer: &Mutex<W>,
+    senders: &SenderMap,
+    control: &Control,
+    context_id: ContextId,
+    error: DecodeError,
+) {
+    if context_id >= CONTROL_CONTEXT_ID {
+        // reserved contexts cannot fail on their own
+        return;
+    }
+
+    match senders.remove(&context_id) {
+        Some((_, route)) => {
+            route.state.malformed.lock().replace(error.clone());
+            route.state.local_end.store(true, Ordering::Release);
+        }
+        None if control.is_new_peer_context(context_id) => {}
+        None => {
+            tracing::trace!("Dropping malformed payload of ended context {}", context_id);
+            return;
+        }
+    }
+    control.end_peer_context(context_id);
+
+    let report = Message {
+        context_id,
+        payload: Payload::Error(Error {
+            error_type: ErrorType::Malformed,
+            message: error.to_string(),
+        }),
+    };
+    if let Err(e) = write_frame(writer, report).await {
+        tracing::warn!("Failed to report malformed payload: {}", e);
+    }
+}
+
+/// Tells the peer why the connection ends and shuts down the writing half.
+async fn protocol_error<W: AsyncWrite + Unpin>(
+    writer: &Mutex<W>,
+    error_type: ErrorType,
+    message: String,
+) {
+    let error = Message {
+        context_id: CONTROL_CONTEXT_ID,
+        payload: Payload::Error(Error {
+            error_type,
+            message,
+        }),
+    };
+    let _ = write_frame(writer, error).await;
+    let _ = writer.lock().await.shutdown().await;
+}
+
+/// Queues a message of a subscribed context, applying `overflow` if the
+/// queue is full.
+///
+/// Reserved contexts never block the reader; once their queue is full, their
+/// payloads are dropped without telling the peer.
+async fn deliver<W: AsyncWrite + Unpin>(
+    route: Route,
+    message: Message,
+    size: usize,
+    writer: &Mutex<W>,
+    senders: &SenderMap,
+    control: &Control,
+    queue: &QueueOptions,
+) {
+    let context_id = message.context_id;
+    let reserved = context_id >= CONTROL_CONTEXT_ID;
+    let payload = message.payload;
+
+    // the end of a context takes no space, so it always fits
+    let bytes = match payload {
+        Payload::Close(_) => 0,
+        _ => size.min(queue.byte_limit() as usize) as u32,
+    };
+
+    if queue.overflow == OverflowPolicy::Block && !reserved {
+        control
+            .stall(async {
+                let bytes = route.bytes.clone().acquire_many_owned(bytes).await.ok();
+                // fails only if the reader is gone
+                let _ = route
+                    .sender
+                    .send(Queued {
+                        payload,
+                        _bytes: bytes,
+                    })
+                    .await;
+            })
+            .await;
+        return;
+    }
+
+    let payload = match route.bytes.clone().try_acquire_many_owned(bytes) {
+        Ok(bytes) => match route.sender.try_send(Queued {
+            payload,
+            _bytes: Some(bytes),
+        }) {
+            Ok(()) | Err(TrySendError::Closed(_)) => return,
+            Err(TrySendError::Full(queued)) => queued.payload,
+        },
+        Err(_) => payload,
+    };
+
+    if matches!(payload, Payload::Close(_)) {
+        // the caller removes the route, ending the channel
+        route.state.ended.store(true, Ordering::Release);
+        return;
+    }
+
+    tracing::debug!("Queue of context {} is full", context_id);
+
+    // the peer is told once per overflow, not once per dropped payload
+    let first = route.state.dropped.fetch_add(1, Ordering::AcqRel) == 0;
+    if reserved {
+        return;
+    }
+
+    if queue.overflow == OverflowPolicy::Close {
+        // later writes fail, and dropping the writers sends no `Close`
+        route.state.local_end.store(true, Ordering::Release);
+        senders.remove(&context_id);
+        control.end_peer_context(context_id);
+    }
+
+    if first {
+        refuse(writer, context_id, "context queue is full", false).await;
+    }
+}
+
+/// Tells the peer that a context ran out of queue space, ending it if `close`
+/// is set because no writer of this side will.
+async fn refuse<W: AsyncWrite + Unpin>(
+    writer: &Mutex<W>,
+    context_id: ContextId,
+    message: &str,
+    close: bool,
+) {
+    let error = Message {
+        context_id,
+        payload: Payload::Error(Error {
+            error_type: ErrorType::ResourceExhausted,
+            message: message.into(),
+        }),
+    };
+    if let Err(e) = write_frame(writer, error).await {
+        tracing::warn!("Failed to report overflow of context {}: {}", context_id, e);
+    }
+
+    if close {
+        let end = Message {
+            context_id,
+            payload: Payload::Close(Close {
+                reason: String::new(),
+            }),
+        };
+        let _ = write_frame(writer, end).await;
+    }
+}
+
+fn match_control(control: &Control, payload: Payload) {
+    match payload {
+        Payload::Close(close) => {
+            let cause = Termination::PeerClosed(Some(close.reason));
+            control.peer_cause.lock().replace(cause);
+            control.end();
+        }
+        Payload::Error(error) => {
+            // the peer reports why it stops reading here, e.g. an oversized
+            // frame, so the connection ends on this side as well
+            tracing::warn!("Peer closed the connection: {}", error.message);
+            let cause = Termination::PeerError(error);
+            control.peer_cause.lock().replace(cause);
+            control.end();
+        }
+        payload => {
+            tracing::warn!("Unexpected payload on the control context: {:?}", payload);
+        }
+    }
+}
+
+enum RecvError {
+    /// The transport failed.
+    Transport(UTPError),
+
+    /// The stream ended between two frames.
+    Ended,
+
+    /// The frame announced more bytes than allowed.
+    Oversized(u64),
+
+    /// The frame could not be decoded.
+    Malformed(MalformedFrame),
+}
+
+impl<E: Into<UTPError>> From<E> for RecvError {
+    fn from(e: E) -> Self {
+        RecvError::Transport(e.into())
+    }
+}
+
+/// Receives the next frame, checking its length before allocating it.
+///
+/// Returns the message together with the size of the frame in bytes.
+async fn recv_frame<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    max_frame_size: u64,
+) -> Result<(Message, usize), RecvError> {
+    // the stream ends cleanly only between two frames
+    let mut len = [0; 8];
+    let read = stream.read(&mut len).await?;
+    if read == 0 {
+        return Err(RecvError::Ended);
+    }
+    stream.read_exact(&mut len[read..]).await?;
+
+    let len = u64::from_le_bytes(len);
+    if len > max_frame_size {
+        return Err(RecvError::Oversized(len));
+    }
 
     let mut buf = vec![0; len as usize];
     stream.read_exact(&mut buf).await?;
 
-    let message = deserialize_message(&buf);
+    let message = deserialize_message(&buf).map_err(RecvError::Malformed)?;
 
-    Ok(message)
+    Ok((message, buf.len()))
 }
 
-fn send_curried<T>(sender: impl Into<UnboundedSender<T>>) -> impl Fn(T) {
-    let sender = sender.into().clone();
-    move |data: T| {
-        if let Err(e) = sender.send(data) {
-            tracing::warn!("Send error while sending to the channel: {:?}", e);
-        }
+fn is_keepalive_probe(message: &Message) -> bool {
+    matches!(message.payload, Payload::Keepalive)
+        && (message.context_id == CLIENT_KEEPALIVE_CONTEXT_ID
+            || message.context_id == SERVER_KEEPALIVE_CONTEXT_ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{constant::CONTROL_CONTEXT_ID, internal::pmc_frame::EndedContexts};
+
+    #[test]
+    fn test_ended_contexts_out_of_order() {
+        let mut ended = EndedContexts::new(0);
+
+        ended.insert(2);
+        assert!(ended.contains(2));
+        assert!(!ended.contains(0));
+        assert!(!ended.contains(4));
+
+        ended.insert(0);
+        assert!(ended.contains(0) && ended.contains(2));
+        assert!(ended.ids.is_empty());
+        assert_eq!(ended.floor, 4);
+    }
+
+    #[test]
+    fn test_ended_contexts_reseed() {
+        let mut ended = EndedContexts::new(0);
+        ended.insert(0);
+        ended.reseed = true;
+
+        // the peer continues with the IDs of its earlier connection
+        assert!(ended.open(100));
+        ended.insert(100);
+        assert!(ended.open(102));
+        ended.insert(102);
+        assert!(ended.ids.is_empty());
+        assert_eq!(ended.floor, 104);
+        assert!(ended.contains(0));
+    }
+
+    #[test]
+    fn test_ended_contexts_wrap() {
+        // the last IDs a client hands out before its counter wraps
+        let last = CONTROL_CONTEXT_ID - 1;
+        let mut ended = EndedContexts::new(0);
+        ended.floor = last - 2;
+
+        ended.insert(last - 2);
+        ended.insert(last);
+        assert_eq!(ended.floor, 2);
+
+        assert!(ended.contains(last));
+        assert!(!ended.contains(2));
+        ended.insert(2);
+        assert!(ended.contains(2));
+        assert!(!ended.contains(4));
     }
 }
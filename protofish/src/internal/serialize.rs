@@ -1,7 +1,10 @@
 use bytes::Bytes;
 use prost::Message;
 
-use crate::{prost_generated::payload::v1, schema};
+use crate::{
+    prost_generated::payload::v1,
+    schema::{self, ContextId, DecodeError},
+};
 
 pub fn serialize_message(message: schema::Message) -> Bytes {
     let message_prost: v1::Message = message.into();
@@ -11,16 +14,36 @@ pub fn serialize_message(message: schema::Message) -> Bytes {
     Bytes::copy_from_slice(&v)
 }
 
-pub fn deserialize_message(buf: &[u8]) -> Option<schema::Message> {
-    v1::Message::decode(buf).ok().map(Into::into)
+/// A received frame that could not be decoded.
+#[derive(Debug)]
+pub struct MalformedFrame {
+    /// Context of the frame, unless the frame is not valid protobuf
+    pub context_id: Option<ContextId>,
+    pub error: DecodeError,
+}
+
+pub fn deserialize_message(buf: &[u8]) -> Result<schema::Message, MalformedFrame> {
+    let message = v1::Message::decode(buf).map_err(|e| MalformedFrame {
+        context_id: None,
+        error: e.into(),
+    })?;
+
+    let context_id = message.context_id;
+    message.try_into().map_err(|error| MalformedFrame {
+        context_id: Some(context_id),
+        error,
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use prost::Message as _;
+
     use crate::{
         constant::VERSION,
         internal::serialize::{deserialize_message, serialize_message},
-        schema::{ClientHello, Message, Payload},
+        prost_generated::payload::v1,
+        schema::{ClientHello, DecodeError, Message, Payload},
     };
 
     #[test]
@@ -31,6 +54,8 @@ mod tests {
                 version: VERSION,
                 resume_connection_token: None,
                 hostname: "example.com".into(),
+                extensions: Default::default(),
+                credentials: None,
             }),
         };
 
@@ -40,4 +65,21 @@ mod tests {
 
         assert_eq!(value.context_id, d.context_id);
     }
+
+    #[test]
+    fn test_deserialize_malformed() {
+        let error = deserialize_message(&[0xff, 0xff]).unwrap_err();
+        assert!(error.context_id.is_none());
+        assert!(matches!(error.error, DecodeError::Protobuf(_)));
+
+        // a payload type unknown to this version decodes as an empty oneof
+        let unknown = v1::Message {
+            context_id: 7,
+            payload: Some(v1::Payload { payload: None }),
+        }
+        .encode_to_vec();
+        let error = deserialize_message(&unknown).unwrap_err();
+        assert_eq!(error.context_id, Some(7));
+        assert!(matches!(error.error, DecodeError::UnknownPayload));
+    }
 }
@@ -68,8 +68,25 @@ pub mod schema;
 pub use schema::*;
 pub mod utp;
 
-pub use core::client::connect;
+pub use core::client::{ConnectOptions, CredentialProvider, connect, connect_with, resume};
 pub use core::common::arbitrary::*;
+pub use core::common::benchmark::BenchmarkResult;
+pub use core::common::channel::ProtofishChannel;
+pub use core::common::codec;
 pub use core::common::connection::*;
-pub use core::server::accept;
+pub use core::common::decode::DecodePolicy;
+pub use core::common::extension;
+pub use core::common::frame::FrameOptions;
+pub use core::common::keepalive::KeepaliveOptions;
+pub use core::common::queue::{OverflowPolicy, QueueOptions};
+#[cfg(feature = "rpc")]
+pub use core::common::rpc;
+pub use core::common::stream::{
+    ProtofishStream, ProtofishStreamReader, ProtofishStreamWriter, UnsplitError,
+};
+pub use core::common::typed::TypedContext;
+pub use core::server::{
+    AcceptOptions, AuthDecision, Authenticator, ConnectionRecord, ConnectionTokenStore,
+    HmacTokenStore, MemoryTokenStore, Principal, accept, accept_with,
+};
 pub use utp::UTP;
@@ -10,5 +10,8 @@ mod payload {
     pub use schema::*;
 }
 
+mod error;
+
 pub use common::*;
+pub use error::DecodeError;
 pub use payload::*;
@@ -1,10 +1,21 @@
-#[derive(Debug, Clone)]
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
     pub major: u32,
     pub minor: u32,
     pub patch: u32,
 }
 
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StreamCreateMeta {
     pub integrity_type: IntegrityType,
@@ -16,8 +27,93 @@ pub enum IntegrityType {
     Unreliable,
 }
 
-#[derive(Debug, Clone)]
+/// Kind of failure reported in an `Error` payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ErrorType {
     Unspecified,
     Timeout,
+    NotFound,
+    PermissionDenied,
+    ResourceExhausted,
+    Cancelled,
+    Internal,
+    /// The peer sent a payload that could not be decoded
+    Malformed,
+    /// Application-defined error code
+    Application(u32),
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorType::Unspecified => f.write_str("unspecified"),
+            ErrorType::Timeout => f.write_str("timeout"),
+            ErrorType::NotFound => f.write_str("not found"),
+            ErrorType::PermissionDenied => f.write_str("permission denied"),
+            ErrorType::ResourceExhausted => f.write_str("resource exhausted"),
+            ErrorType::Cancelled => f.write_str("cancelled"),
+            ErrorType::Internal => f.write_str("internal"),
+            ErrorType::Malformed => f.write_str("malformed"),
+            ErrorType::Application(code) => write!(f, "application error {}", code),
+        }
+    }
+}
+
+/// Optional protocol features advertised in the hello exchange.
+///
+/// Each entry maps an extension name to its value. Well-known extensions are
+/// listed in [`crate::extension`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions {
+    entries: BTreeMap<String, String>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces an extension.
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Adds or replaces an extension.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.insert(name.into(), value.into());
+    }
+
+    /// Returns the value of an extension.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// Returns `true` if the extension is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over all extension names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl From<HashMap<String, String>> for Extensions {
+    fn from(entries: HashMap<String, String>) -> Self {
+        Self {
+            entries: entries.into_iter().collect(),
+        }
+    }
+}
+
+impl From<Extensions> for HashMap<String, String> {
+    fn from(value: Extensions) -> Self {
+        value.entries.into_iter().collect()
+    }
 }
@@ -1,6 +1,9 @@
 use crate::{
     prost_generated::common::{self},
-    schema::common::schema::{ErrorType, IntegrityType, StreamCreateMeta, Version},
+    schema::{
+        DecodeError,
+        common::schema::{ErrorType, IntegrityType, StreamCreateMeta, Version},
+    },
 };
 
 impl From<common::v1::Version> for Version {
@@ -23,13 +26,21 @@ impl From<Version> for common::v1::Version {
     }
 }
 
-impl From<common::v1::StreamCreateMeta> for StreamCreateMeta {
-    fn from(value: common::v1::StreamCreateMeta) -> Self {
-        StreamCreateMeta {
-            integrity_type: common::v1::IntegrityType::try_from(value.stream_integrity)
-                .unwrap()
-                .into(),
-        }
+impl TryFrom<common::v1::StreamCreateMeta> for StreamCreateMeta {
+    type Error = DecodeError;
+
+    fn try_from(value: common::v1::StreamCreateMeta) -> Result<Self, DecodeError> {
+        let integrity_type =
+            common::v1::IntegrityType::try_from(value.stream_integrity).map_err(|_| {
+                DecodeError::InvalidEnum {
+                    field: "stream_integrity",
+                    value: value.stream_integrity,
+                }
+            })?;
+
+        Ok(StreamCreateMeta {
+            integrity_type: integrity_type.into(),
+        })
     }
 }
 
@@ -53,12 +64,25 @@ impl From<IntegrityType> for common::v1::IntegrityType {
     }
 }
 
-impl From<common::v1::ErrorType> for ErrorType {
-    fn from(value: common::v1::ErrorType) -> Self {
-        match value {
+/// Fails for `Application`, whose code is carried next to the type in
+/// `payload::v1::Error`.
+impl TryFrom<common::v1::ErrorType> for ErrorType {
+    type Error = DecodeError;
+
+    fn try_from(value: common::v1::ErrorType) -> Result<Self, DecodeError> {
+        Ok(match value {
             common::v1::ErrorType::Unspecified => ErrorType::Unspecified,
             common::v1::ErrorType::Timeout => ErrorType::Timeout,
-        }
+            common::v1::ErrorType::NotFound => ErrorType::NotFound,
+            common::v1::ErrorType::PermissionDenied => ErrorType::PermissionDenied,
+            common::v1::ErrorType::ResourceExhausted => ErrorType::ResourceExhausted,
+            common::v1::ErrorType::Cancelled => ErrorType::Cancelled,
+            common::v1::ErrorType::Internal => ErrorType::Internal,
+            common::v1::ErrorType::Malformed => ErrorType::Malformed,
+            common::v1::ErrorType::Application => {
+                return Err(DecodeError::MissingField("application_code"));
+            }
+        })
     }
 }
 
@@ -67,6 +91,13 @@ impl From<ErrorType> for common::v1::ErrorType {
         match value {
             ErrorType::Unspecified => common::v1::ErrorType::Unspecified,
             ErrorType::Timeout => common::v1::ErrorType::Timeout,
+            ErrorType::NotFound => common::v1::ErrorType::NotFound,
+            ErrorType::PermissionDenied => common::v1::ErrorType::PermissionDenied,
+            ErrorType::ResourceExhausted => common::v1::ErrorType::ResourceExhausted,
+            ErrorType::Cancelled => common::v1::ErrorType::Cancelled,
+            ErrorType::Internal => common::v1::ErrorType::Internal,
+            ErrorType::Malformed => common::v1::ErrorType::Malformed,
+            ErrorType::Application(_) => common::v1::ErrorType::Application,
         }
     }
 }
@@ -96,7 +127,7 @@ mod tests {
         let proto_meta = common::v1::StreamCreateMeta {
             stream_integrity: common::v1::IntegrityType::Reliable.into(),
         };
-        let schema_meta: StreamCreateMeta = proto_meta.clone().into();
+        let schema_meta: StreamCreateMeta = proto_meta.clone().try_into().unwrap();
         assert!(matches!(
             schema_meta.integrity_type,
             IntegrityType::Reliable
@@ -132,11 +163,11 @@ mod tests {
     #[test]
     fn test_error_type_conversion() {
         let proto_unspecified = common::v1::ErrorType::Unspecified;
-        let schema_unspecified: ErrorType = proto_unspecified.into();
+        let schema_unspecified: ErrorType = proto_unspecified.try_into().unwrap();
         assert!(matches!(schema_unspecified, ErrorType::Unspecified));
 
         let proto_timeout = common::v1::ErrorType::Timeout;
-        let schema_timeout: ErrorType = proto_timeout.into();
+        let schema_timeout: ErrorType = proto_timeout.try_into().unwrap();
         assert!(matches!(schema_timeout, ErrorType::Timeout));
 
         let schema_unspecified_back: common::v1::ErrorType = ErrorType::Unspecified.into();
@@ -144,5 +175,14 @@ mod tests {
 
         let schema_timeout_back: common::v1::ErrorType = ErrorType::Timeout.into();
         assert_eq!(schema_timeout_back, common::v1::ErrorType::Timeout);
+
+        let proto_not_found = common::v1::ErrorType::NotFound;
+        let schema_not_found: ErrorType = proto_not_found.try_into().unwrap();
+        assert_eq!(schema_not_found, ErrorType::NotFound);
+
+        assert!(ErrorType::try_from(common::v1::ErrorType::Application).is_err());
+
+        let schema_application_back: common::v1::ErrorType = ErrorType::Application(42).into();
+        assert_eq!(schema_application_back, common::v1::ErrorType::Application);
     }
 }
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+/// Errors that can occur while decoding a message received from the peer.
+#[derive(Debug, Clone, Error)]
+pub enum DecodeError {
+    /// The bytes are not a valid protobuf message
+    #[error("invalid protobuf: {0}")]
+    Protobuf(#[from] prost::DecodeError),
+
+    /// The payload is of a type this version does not know, or empty
+    #[error("unknown payload")]
+    UnknownPayload,
+
+    /// A required field is missing
+    #[error("missing field `{0}`")]
+    MissingField(&'static str),
+
+    /// An enum field holds a value this version does not know
+    #[error("invalid value {value} of `{field}`")]
+    InvalidEnum { field: &'static str, value: i32 },
+}
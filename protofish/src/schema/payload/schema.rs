@@ -1,6 +1,6 @@
 use bytes::Bytes;
 
-use crate::schema::{IntegrityType, Version};
+use crate::schema::{Extensions, IntegrityType, Version};
 
 pub type ContextId = u64;
 pub type StreamId = u64;
@@ -21,9 +21,11 @@ pub enum Payload {
     StreamClose(StreamClose),
     ArbitaryData(ArbitaryData),
     Keepalive,
-    Close,
+    Close(Close),
     BenchmarkStart(BenchmarkStart),
-    BenchmarkEnd,
+    BenchmarkEnd(BenchmarkEnd),
+    AuthChallenge(AuthChallenge),
+    AuthResponse(AuthResponse),
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +33,8 @@ pub struct ClientHello {
     pub version: Version,
     pub resume_connection_token: Option<Vec<u8>>,
     pub hostname: String,
+    pub extensions: Extensions,
+    pub credentials: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +43,7 @@ pub struct ServerHello {
     pub ok: bool,
     pub connection_token: Option<Bytes>,
     pub message: Option<String>,
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone)]
@@ -63,8 +68,30 @@ pub struct ArbitaryData {
     pub content: Vec<u8>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Close {
+    pub reason: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct BenchmarkStart {
     pub integrity_type: IntegrityType,
     pub byte_count: u64,
 }
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkEnd {
+    pub byte_count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthChallenge {
+    pub mechanisms: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthResponse {
+    pub mechanism: String,
+    pub data: Vec<u8>,
+}
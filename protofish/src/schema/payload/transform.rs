@@ -1,14 +1,21 @@
 use crate::{
-    prost_generated::common::v1 as common_v1, prost_generated::payload::v1 as payload_v1,
-    schema as common_schema, schema::payload::schema as payload_schema,
+    prost_generated::common::v1 as common_v1,
+    prost_generated::payload::v1 as payload_v1,
+    schema as common_schema,
+    schema::{DecodeError, payload::schema as payload_schema},
 };
 
-impl From<payload_v1::Message> for payload_schema::Message {
-    fn from(value: payload_v1::Message) -> Self {
-        payload_schema::Message {
+impl TryFrom<payload_v1::Message> for payload_schema::Message {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::Message) -> Result<Self, DecodeError> {
+        Ok(payload_schema::Message {
             context_id: value.context_id,
-            payload: value.payload.unwrap().into(),
-        }
+            payload: value
+                .payload
+                .ok_or(DecodeError::UnknownPayload)?
+                .try_into()?,
+        })
     }
 }
 
@@ -21,16 +28,21 @@ impl From<payload_schema::Message> for payload_v1::Message {
     }
 }
 
-impl From<payload_v1::Payload> for payload_schema::Payload {
-    fn from(value: payload_v1::Payload) -> Self {
-        match value.payload.unwrap() {
+impl TryFrom<payload_v1::Payload> for payload_schema::Payload {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::Payload) -> Result<Self, DecodeError> {
+        // prost leaves the oneof empty for payload types added by newer peers
+        let payload = value.payload.ok_or(DecodeError::UnknownPayload)?;
+
+        Ok(match payload {
             payload_v1::payload::Payload::ClientHello(v) => {
-                payload_schema::Payload::ClientHello(v.into())
+                payload_schema::Payload::ClientHello(v.try_into()?)
             }
             payload_v1::payload::Payload::Ok(_) => payload_schema::Payload::Ok,
             payload_v1::payload::Payload::Error(v) => payload_schema::Payload::Error(v.into()),
             payload_v1::payload::Payload::StreamOpen(v) => {
-                payload_schema::Payload::StreamOpen(v.into())
+                payload_schema::Payload::StreamOpen(v.try_into()?)
             }
             payload_v1::payload::Payload::StreamClose(v) => {
                 payload_schema::Payload::StreamClose(v.into())
@@ -40,14 +52,22 @@ impl From<payload_v1::Payload> for payload_schema::Payload {
             }
             payload_v1::payload::Payload::Keepalive(_) => payload_schema::Payload::Keepalive,
             payload_v1::payload::Payload::ServerHello(v) => {
-                payload_schema::Payload::ServerHello(v.into())
+                payload_schema::Payload::ServerHello(v.try_into()?)
             }
-            payload_v1::payload::Payload::Close(_) => payload_schema::Payload::Close,
+            payload_v1::payload::Payload::Close(v) => payload_schema::Payload::Close(v.into()),
             payload_v1::payload::Payload::BenchmarkStart(v) => {
-                payload_schema::Payload::BenchmarkStart(v.into())
+                payload_schema::Payload::BenchmarkStart(v.try_into()?)
             }
-            payload_v1::payload::Payload::BenchmarkEnd(_) => payload_schema::Payload::BenchmarkEnd,
-        }
+            payload_v1::payload::Payload::BenchmarkEnd(v) => {
+                payload_schema::Payload::BenchmarkEnd(v.into())
+            }
+            payload_v1::payload::Payload::AuthChallenge(v) => {
+                payload_schema::Payload::AuthChallenge(v.into())
+            }
+            payload_v1::payload::Payload::AuthResponse(v) => {
+                payload_schema::Payload::AuthResponse(v.into())
+            }
+        })
     }
 }
 
@@ -74,14 +94,18 @@ impl From<payload_schema::Payload> for payload_v1::Payload {
             payload_schema::Payload::ServerHello(v) => {
                 payload_v1::payload::Payload::ServerHello(v.into())
             }
-            payload_schema::Payload::Close => {
-                payload_v1::payload::Payload::Close(payload_v1::Close {})
-            }
+            payload_schema::Payload::Close(v) => payload_v1::payload::Payload::Close(v.into()),
             payload_schema::Payload::BenchmarkStart(v) => {
                 payload_v1::payload::Payload::BenchmarkStart(v.into())
             }
-            payload_schema::Payload::BenchmarkEnd => {
-                payload_v1::payload::Payload::BenchmarkEnd(payload_v1::BenchmarkEnd {})
+            payload_schema::Payload::BenchmarkEnd(v) => {
+                payload_v1::payload::Payload::BenchmarkEnd(v.into())
+            }
+            payload_schema::Payload::AuthChallenge(v) => {
+                payload_v1::payload::Payload::AuthChallenge(v.into())
+            }
+            payload_schema::Payload::AuthResponse(v) => {
+                payload_v1::payload::Payload::AuthResponse(v.into())
             }
         };
 
@@ -91,13 +115,20 @@ impl From<payload_schema::Payload> for payload_v1::Payload {
     }
 }
 
-impl From<payload_v1::ClientHello> for payload_schema::ClientHello {
-    fn from(value: payload_v1::ClientHello) -> Self {
-        payload_schema::ClientHello {
-            version: value.version.unwrap().into(),
+impl TryFrom<payload_v1::ClientHello> for payload_schema::ClientHello {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::ClientHello) -> Result<Self, DecodeError> {
+        Ok(payload_schema::ClientHello {
+            version: value
+                .version
+                .ok_or(DecodeError::MissingField("version"))?
+                .into(),
             resume_connection_token: value.resume_connection_token,
             hostname: value.hostname,
-        }
+            extensions: value.extensions.into(),
+            credentials: value.credentials,
+        })
     }
 }
 
@@ -107,18 +138,26 @@ impl From<payload_schema::ClientHello> for payload_v1::ClientHello {
             version: Some(value.version.into()),
             resume_connection_token: value.resume_connection_token,
             hostname: value.hostname,
+            extensions: value.extensions.into(),
+            credentials: value.credentials,
         }
     }
 }
 
-impl From<payload_v1::ServerHello> for payload_schema::ServerHello {
-    fn from(value: payload_v1::ServerHello) -> Self {
-        payload_schema::ServerHello {
-            version: value.version.unwrap().into(),
+impl TryFrom<payload_v1::ServerHello> for payload_schema::ServerHello {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::ServerHello) -> Result<Self, DecodeError> {
+        Ok(payload_schema::ServerHello {
+            version: value
+                .version
+                .ok_or(DecodeError::MissingField("version"))?
+                .into(),
             ok: value.ok,
             connection_token: value.connection_token.map(Into::into),
             message: value.message,
-        }
+            extensions: value.extensions.into(),
+        })
     }
 }
 
@@ -129,16 +168,26 @@ impl From<payload_schema::ServerHello> for payload_v1::ServerHello {
             ok: value.ok,
             connection_token: value.connection_token.map(Into::into),
             message: value.message,
+            extensions: value.extensions.into(),
         }
     }
 }
 
 impl From<payload_v1::Error> for payload_schema::Error {
     fn from(value: payload_v1::Error) -> Self {
+        // types added by newer peers are reported as unspecified
+        let error_type = match common_v1::ErrorType::try_from(value.error_type) {
+            Ok(common_v1::ErrorType::Application) => {
+                common_schema::ErrorType::Application(value.application_code)
+            }
+            Ok(error_type) => error_type
+                .try_into()
+                .unwrap_or(common_schema::ErrorType::Unspecified),
+            Err(_) => common_schema::ErrorType::Unspecified,
+        };
+
         payload_schema::Error {
-            error_type: common_v1::ErrorType::try_from(value.error_type)
-                .unwrap()
-                .into(),
+            error_type,
             message: value.message,
         }
     }
@@ -146,19 +195,30 @@ impl From<payload_v1::Error> for payload_schema::Error {
 
 impl From<payload_schema::Error> for payload_v1::Error {
     fn from(value: payload_schema::Error) -> Self {
+        let application_code = match value.error_type {
+            common_schema::ErrorType::Application(code) => code,
+            _ => 0,
+        };
+
         payload_v1::Error {
             error_type: value.error_type.into(),
             message: value.message,
+            application_code,
         }
     }
 }
 
-impl From<payload_v1::StreamOpen> for payload_schema::StreamOpen {
-    fn from(value: payload_v1::StreamOpen) -> Self {
-        payload_schema::StreamOpen {
+impl TryFrom<payload_v1::StreamOpen> for payload_schema::StreamOpen {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::StreamOpen) -> Result<Self, DecodeError> {
+        Ok(payload_schema::StreamOpen {
             stream_id: value.stream_id,
-            meta: value.meta.unwrap().into(),
-        }
+            meta: value
+                .meta
+                .ok_or(DecodeError::MissingField("meta"))?
+                .try_into()?,
+        })
     }
 }
 
@@ -171,6 +231,22 @@ impl From<payload_schema::StreamOpen> for payload_v1::StreamOpen {
     }
 }
 
+impl From<payload_v1::Close> for payload_schema::Close {
+    fn from(value: payload_v1::Close) -> Self {
+        payload_schema::Close {
+            reason: value.reason,
+        }
+    }
+}
+
+impl From<payload_schema::Close> for payload_v1::Close {
+    fn from(value: payload_schema::Close) -> Self {
+        payload_v1::Close {
+            reason: value.reason,
+        }
+    }
+}
+
 impl From<payload_v1::StreamClose> for payload_schema::StreamClose {
     fn from(value: payload_v1::StreamClose) -> Self {
         payload_schema::StreamClose {
@@ -203,14 +279,22 @@ impl From<payload_schema::ArbitaryData> for payload_v1::ArbitaryData {
     }
 }
 
-impl From<payload_v1::BenchmarkStart> for payload_schema::BenchmarkStart {
-    fn from(value: payload_v1::BenchmarkStart) -> Self {
-        payload_schema::BenchmarkStart {
-            integrity_type: common_v1::IntegrityType::try_from(value.integrity_type)
-                .unwrap()
-                .into(),
+impl TryFrom<payload_v1::BenchmarkStart> for payload_schema::BenchmarkStart {
+    type Error = DecodeError;
+
+    fn try_from(value: payload_v1::BenchmarkStart) -> Result<Self, DecodeError> {
+        let integrity_type =
+            common_v1::IntegrityType::try_from(value.integrity_type).map_err(|_| {
+                DecodeError::InvalidEnum {
+                    field: "integrity_type",
+                    value: value.integrity_type,
+                }
+            })?;
+
+        Ok(payload_schema::BenchmarkStart {
+            integrity_type: integrity_type.into(),
             byte_count: value.byte_count,
-        }
+        })
     }
 }
 
@@ -223,6 +307,58 @@ impl From<payload_schema::BenchmarkStart> for payload_v1::BenchmarkStart {
     }
 }
 
+impl From<payload_v1::BenchmarkEnd> for payload_schema::BenchmarkEnd {
+    fn from(value: payload_v1::BenchmarkEnd) -> Self {
+        payload_schema::BenchmarkEnd {
+            byte_count: value.byte_count,
+        }
+    }
+}
+
+impl From<payload_schema::BenchmarkEnd> for payload_v1::BenchmarkEnd {
+    fn from(value: payload_schema::BenchmarkEnd) -> Self {
+        payload_v1::BenchmarkEnd {
+            byte_count: value.byte_count,
+        }
+    }
+}
+
+impl From<payload_v1::AuthChallenge> for payload_schema::AuthChallenge {
+    fn from(value: payload_v1::AuthChallenge) -> Self {
+        payload_schema::AuthChallenge {
+            mechanisms: value.mechanisms,
+            data: value.data,
+        }
+    }
+}
+
+impl From<payload_schema::AuthChallenge> for payload_v1::AuthChallenge {
+    fn from(value: payload_schema::AuthChallenge) -> Self {
+        payload_v1::AuthChallenge {
+            mechanisms: value.mechanisms,
+            data: value.data,
+        }
+    }
+}
+
+impl From<payload_v1::AuthResponse> for payload_schema::AuthResponse {
+    fn from(value: payload_v1::AuthResponse) -> Self {
+        payload_schema::AuthResponse {
+            mechanism: value.mechanism,
+            data: value.data,
+        }
+    }
+}
+
+impl From<payload_schema::AuthResponse> for payload_v1::AuthResponse {
+    fn from(value: payload_schema::AuthResponse) -> Self {
+        payload_v1::AuthResponse {
+            mechanism: value.mechanism,
+            data: value.data,
+        }
+    }
+}
+
 impl From<common_schema::StreamCreateMeta> for common_v1::StreamCreateMeta {
     fn from(value: common_schema::StreamCreateMeta) -> Self {
         common_v1::StreamCreateMeta {
@@ -233,11 +369,7 @@ impl From<common_schema::StreamCreateMeta> for common_v1::StreamCreateMeta {
 
 impl From<common_schema::ErrorType> for i32 {
     fn from(value: common_schema::ErrorType) -> Self {
-        (match value {
-            common_schema::ErrorType::Unspecified => common_v1::ErrorType::Unspecified,
-            common_schema::ErrorType::Timeout => common_v1::ErrorType::Timeout,
-        })
-        .into()
+        common_v1::ErrorType::from(value).into()
     }
 }
 
@@ -264,7 +396,7 @@ mod tests {
                 payload: Some(payload_v1::payload::Payload::Ok(payload_v1::Ok {})),
             }),
         };
-        let schema_message: payload_schema::Message = proto_message.clone().into();
+        let schema_message: payload_schema::Message = proto_message.clone().try_into().unwrap();
         assert_eq!(schema_message.context_id, 123);
         assert!(matches!(
             schema_message.payload,
@@ -286,13 +418,15 @@ mod tests {
             }),
             hostname: "example.com".into(),
             resume_connection_token: None,
+            extensions: Default::default(),
+            credentials: None,
         };
         let payload = payload_v1::Payload {
             payload: Some(payload_v1::payload::Payload::ClientHello(
                 proto_client_hello,
             )),
         };
-        let schema_payload: payload_schema::Payload = payload.into();
+        let schema_payload: payload_schema::Payload = payload.try_into().unwrap();
         assert!(matches!(
             schema_payload,
             payload_schema::Payload::ClientHello(_)
@@ -302,7 +436,7 @@ mod tests {
         let payload = payload_v1::Payload {
             payload: Some(payload_v1::payload::Payload::Ok(payload_v1::Ok {})),
         };
-        let schema_payload: payload_schema::Payload = payload.into();
+        let schema_payload: payload_schema::Payload = payload.try_into().unwrap();
         assert!(matches!(schema_payload, payload_schema::Payload::Ok));
     }
 
@@ -316,13 +450,20 @@ mod tests {
             }),
             hostname: "example.com".into(),
             resume_connection_token: Some(vec![1, 2, 3]),
+            extensions: [("compression".to_string(), "zstd".to_string())].into(),
+            credentials: Some(b"secret".to_vec()),
         };
-        let schema_client_hello: payload_schema::ClientHello = proto_client_hello.clone().into();
+        let schema_client_hello: payload_schema::ClientHello =
+            proto_client_hello.clone().try_into().unwrap();
         assert_eq!(schema_client_hello.version.major, 1);
         assert_eq!(
             schema_client_hello.resume_connection_token,
             Some(vec![1, 2, 3])
         );
+        assert_eq!(
+            schema_client_hello.extensions.get("compression"),
+            Some("zstd")
+        );
 
         let converted_proto: payload_v1::ClientHello = schema_client_hello.into();
         assert_eq!(converted_proto, proto_client_hello);
@@ -339,8 +480,10 @@ mod tests {
             ok: true,
             connection_token: Some(vec![4, 5, 6]),
             message: Some("hi".into()),
+            extensions: Default::default(),
         };
-        let schema_server_hello: payload_schema::ServerHello = proto_server_hello.clone().into();
+        let schema_server_hello: payload_schema::ServerHello =
+            proto_server_hello.clone().try_into().unwrap();
         assert_eq!(schema_server_hello.version.major, 1);
         assert!(schema_server_hello.ok);
         assert!(schema_server_hello.connection_token.is_some());
@@ -354,6 +497,7 @@ mod tests {
         let proto_error = payload_v1::Error {
             error_type: common_v1::ErrorType::Timeout.into(),
             message: "Request timed out".to_string(),
+            application_code: 0,
         };
         let schema_error: payload_schema::Error = proto_error.clone().into();
         assert!(matches!(schema_error.error_type, ErrorType::Timeout));
@@ -363,6 +507,28 @@ mod tests {
         assert_eq!(converted_proto, proto_error);
     }
 
+    #[test]
+    fn test_application_error_conversion() {
+        let proto_error = payload_v1::Error {
+            error_type: common_v1::ErrorType::Application.into(),
+            message: "Quota exceeded".to_string(),
+            application_code: 42,
+        };
+        let schema_error: payload_schema::Error = proto_error.clone().into();
+        assert_eq!(schema_error.error_type, ErrorType::Application(42));
+
+        let converted_proto: payload_v1::Error = schema_error.into();
+        assert_eq!(converted_proto, proto_error);
+
+        let unknown_error = payload_v1::Error {
+            error_type: 1000,
+            message: String::new(),
+            application_code: 0,
+        };
+        let schema_error: payload_schema::Error = unknown_error.into();
+        assert_eq!(schema_error.error_type, ErrorType::Unspecified);
+    }
+
     #[test]
     fn test_stream_open_conversion() {
         let proto_stream_open = payload_v1::StreamOpen {
@@ -371,7 +537,8 @@ mod tests {
                 stream_integrity: common_v1::IntegrityType::Reliable.into(),
             }),
         };
-        let schema_stream_open: payload_schema::StreamOpen = proto_stream_open.clone().into();
+        let schema_stream_open: payload_schema::StreamOpen =
+            proto_stream_open.clone().try_into().unwrap();
         assert_eq!(schema_stream_open.stream_id, 12345);
         assert!(matches!(
             schema_stream_open.meta.integrity_type,
@@ -411,7 +578,7 @@ mod tests {
             byte_count: 1024,
         };
         let schema_benchmark_start: payload_schema::BenchmarkStart =
-            proto_benchmark_start.clone().into();
+            proto_benchmark_start.clone().try_into().unwrap();
         assert!(matches!(
             schema_benchmark_start.integrity_type,
             IntegrityType::Unreliable
@@ -421,4 +588,74 @@ mod tests {
         let converted_proto: payload_v1::BenchmarkStart = schema_benchmark_start.into();
         assert_eq!(converted_proto, proto_benchmark_start);
     }
+
+    #[test]
+    fn test_auth_challenge_conversion() {
+        let proto_challenge = payload_v1::AuthChallenge {
+            mechanisms: vec!["hmac-sha256".into()],
+            data: vec![7; 32],
+        };
+        let schema_challenge: payload_schema::AuthChallenge = proto_challenge.clone().into();
+        assert_eq!(schema_challenge.mechanisms, vec!["hmac-sha256"]);
+        assert_eq!(schema_challenge.data, vec![7; 32]);
+
+        let converted_proto: payload_v1::AuthChallenge = schema_challenge.into();
+        assert_eq!(converted_proto, proto_challenge);
+    }
+
+    #[test]
+    fn test_auth_response_conversion() {
+        let proto_response = payload_v1::AuthResponse {
+            mechanism: "hmac-sha256".into(),
+            data: vec![1, 2, 3],
+        };
+        let schema_response: payload_schema::AuthResponse = proto_response.clone().into();
+        assert_eq!(schema_response.mechanism, "hmac-sha256");
+        assert_eq!(schema_response.data, vec![1, 2, 3]);
+
+        let converted_proto: payload_v1::AuthResponse = schema_response.into();
+        assert_eq!(converted_proto, proto_response);
+    }
+
+    #[test]
+    fn test_malformed_conversion() {
+        let empty = payload_v1::Message {
+            context_id: 1,
+            payload: Some(payload_v1::Payload { payload: None }),
+        };
+        assert!(matches!(
+            payload_schema::Message::try_from(empty),
+            Err(DecodeError::UnknownPayload)
+        ));
+
+        let no_version = payload_v1::ServerHello {
+            version: None,
+            ..Default::default()
+        };
+        assert!(matches!(
+            payload_schema::ServerHello::try_from(no_version),
+            Err(DecodeError::MissingField("version"))
+        ));
+
+        let no_meta = payload_v1::StreamOpen {
+            stream_id: 1,
+            meta: None,
+        };
+        assert!(matches!(
+            payload_schema::StreamOpen::try_from(no_meta),
+            Err(DecodeError::MissingField("meta"))
+        ));
+
+        let bad_integrity = payload_v1::BenchmarkStart {
+            integrity_type: 42,
+            byte_count: 0,
+        };
+        assert!(matches!(
+            payload_schema::BenchmarkStart::try_from(bad_integrity),
+            Err(DecodeError::InvalidEnum {
+                field: "integrity_type",
+                value: 42
+            })
+        ));
+    }
 }
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::utp::error::UTPError;
+
+/// Trait defining the interface for a message-preserving unreliable channel.
+///
+/// Unlike the byte-oriented halves of a [`super::UTPStream`], every message
+/// is delivered whole or not at all. Messages may be lost or reordered, but
+/// never split or merged.
+#[async_trait]
+pub trait UnreliableChannel: Send + Sync + 'static {
+    /// Returns the size of the largest message [`UnreliableChannel::send`]
+    /// accepts, i.e. the datagram MTU minus the transport's own framing.
+    fn max_message_size(&self) -> usize;
+
+    /// Sends a message as a single datagram.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UTPError::MessageTooLarge` if the message exceeds
+    /// [`UnreliableChannel::max_message_size`], or an error if the transport
+    /// fails to send it.
+    async fn send(&self, message: Bytes) -> Result<(), UTPError>;
+
+    /// Waits for the next message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UTPError::ChannelClosed` once the peer closed the channel.
+    async fn recv(&self) -> Result<Bytes, UTPError>;
+}
@@ -13,4 +13,16 @@ pub enum UTPError {
 
     #[error("UTP IO error {0}")]
     Io(#[from] std::io::Error),
+
+    /// A message does not fit into a single datagram
+    #[error("UTP message of {size} bytes exceeds the datagram MTU of {max} bytes")]
+    MessageTooLarge { size: usize, max: usize },
+
+    /// The stream cannot be used as an [`super::UnreliableChannel`]
+    #[error("UTP stream does not support unreliable channels")]
+    ChannelUnsupported,
+
+    /// The peer closed the channel
+    #[error("UTP channel closed")]
+    ChannelClosed,
 }
@@ -2,7 +2,9 @@
 
 pub mod error;
 
+mod channel;
 mod protocol;
+pub use channel::*;
 pub use protocol::*;
 pub use tests::utp::mock_utp_pairs;
 
@@ -1,9 +1,12 @@
+use std::net::SocketAddr;
+
 use async_trait::async_trait;
+use bytes::Bytes;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
     schema::{IntegrityType, StreamId},
-    utp::error::UTPError,
+    utp::{UnreliableChannel, error::UTPError},
 };
 
 /// Trait defining the interface for a UTP stream.
@@ -14,6 +17,7 @@ use crate::{
 pub trait UTPStream: Send + Sync + 'static {
     type StreamRead: AsyncRead + Unpin + Send;
     type StreamWrite: AsyncWrite + Unpin + Send;
+    type Channel: UnreliableChannel;
 
     /// Returns the unique identifier for this stream.
     fn id(&self) -> StreamId;
@@ -22,6 +26,17 @@ pub trait UTPStream: Send + Sync + 'static {
     fn integrity_type(&self) -> IntegrityType;
 
     fn split(self) -> (Self::StreamWrite, Self::StreamRead);
+
+    /// Turns the stream into a channel of whole messages.
+    ///
+    /// Streams opened as `Unreliable` must support this; each message maps
+    /// to one datagram of the transport.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UTPError::ChannelUnsupported` if the stream cannot carry
+    /// messages, e.g. a reliable stream of a transport without framing.
+    fn into_channel(self) -> Result<Self::Channel, UTPError>;
 }
 
 /// Trait defining the Upstream Transport Protocol (UTP) interface.
@@ -51,6 +66,14 @@ pub trait UTP: Send + Sync + 'static {
     /// Returns an error if the connection fails.
     async fn connect(&self, hostname: &str) -> Result<(), UTPError>;
 
+    /// Returns what the transport knows about the identity of the peer.
+    ///
+    /// This is handed to the server-side authenticator during the handshake.
+    /// The default implementation reports an unknown peer.
+    fn identity(&self) -> TransportIdentity {
+        TransportIdentity::default()
+    }
+
     /// Waits for the next UTP event.
     ///
     /// This method blocks until an event occurs, such as a new incoming
@@ -86,6 +109,23 @@ pub trait UTP: Send + Sync + 'static {
         id: StreamId,
         integrity: IntegrityType,
     ) -> Result<Self::Stream, UTPError>;
+
+    /// Releases the receiving side of a stream the peer closed with
+    /// `StreamClose`.
+    ///
+    /// Implementations should let the stream's reader reach its end once
+    /// buffered data is consumed, and drop data arriving afterwards. This
+    /// matters for transports without a stream-level FIN. The default
+    /// implementation does nothing.
+    fn release_stream(&self, id: StreamId) {
+        let _ = id;
+    }
+
+    /// Closes the underlying transport connection.
+    ///
+    /// This is called once a Protofish connection is closed gracefully. The
+    /// default implementation does nothing.
+    async fn close(&self) {}
 }
 
 /// Events that can occur on a UTP connection.
@@ -97,3 +137,13 @@ pub enum UTPEvent {
     /// A new stream with the given ID has been opened by the peer
     NewStream(StreamId),
 }
+
+/// Identity of the remote peer as established by the transport.
+#[derive(Clone, Debug, Default)]
+pub struct TransportIdentity {
+    /// Network address of the peer, if the transport has one
+    pub remote_address: Option<SocketAddr>,
+
+    /// DER-encoded certificate chain presented by the peer, if any
+    pub peer_certificates: Vec<Bytes>,
+}
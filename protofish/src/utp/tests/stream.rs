@@ -1,6 +1,20 @@
-use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+use std::io::ErrorKind;
 
-use crate::{IntegrityType, schema::StreamId, utp::protocol::UTPStream};
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf},
+    sync::Mutex,
+};
+
+use crate::{
+    IntegrityType,
+    schema::StreamId,
+    utp::{UnreliableChannel, error::UTPError, protocol::UTPStream},
+};
+
+/// Datagram MTU reported by mock channels
+pub const MOCK_MAX_MESSAGE_SIZE: usize = 1200;
 
 pub struct MockUTPStream {
     pub id: StreamId,
@@ -16,6 +30,7 @@ impl MockUTPStream {
 impl UTPStream for MockUTPStream {
     type StreamRead = ReadHalf<DuplexStream>;
     type StreamWrite = WriteHalf<DuplexStream>;
+    type Channel = MockChannel;
 
     fn id(&self) -> StreamId {
         self.id
@@ -31,6 +46,58 @@ impl UTPStream for MockUTPStream {
         let (a, b) = tokio::io::split(self.stream);
         (b, a)
     }
+
+    fn into_channel(self) -> Result<MockChannel, UTPError> {
+        let (reader, writer) = tokio::io::split(self.stream);
+
+        Ok(MockChannel {
+            reader: reader.into(),
+            writer: writer.into(),
+        })
+    }
+}
+
+/// Length-prefixed messages over a mock stream.
+pub struct MockChannel {
+    reader: Mutex<ReadHalf<DuplexStream>>,
+    writer: Mutex<WriteHalf<DuplexStream>>,
+}
+
+#[async_trait]
+impl UnreliableChannel for MockChannel {
+    fn max_message_size(&self) -> usize {
+        MOCK_MAX_MESSAGE_SIZE
+    }
+
+    async fn send(&self, message: Bytes) -> Result<(), UTPError> {
+        if message.len() > MOCK_MAX_MESSAGE_SIZE {
+            return Err(UTPError::MessageTooLarge {
+                size: message.len(),
+                max: MOCK_MAX_MESSAGE_SIZE,
+            });
+        }
+
+        let mut writer = self.writer.lock().await;
+        writer.write_u32_le(message.len() as u32).await?;
+        writer.write_all(&message).await?;
+
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Bytes, UTPError> {
+        let mut reader = self.reader.lock().await;
+
+        let len = match reader.read_u32_le().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(UTPError::ChannelClosed),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut message = vec![0; len as usize];
+        reader.read_exact(&mut message).await?;
+
+        Ok(message.into())
+    }
 }
 
 pub fn mock_utp_stream_pairs(id: StreamId) -> (MockUTPStream, MockUTPStream) {
@@ -47,7 +47,7 @@ async fn handle_connection(quinn_conn: quinn::Connection) -> anyhow::Result<()>
     println!("Protofish connection established");
 
     // Accept new arbitrary contexts (streams)
-    while let Some(arb) = pf_conn.next_arb().await {
+    while let Ok(arb) = pf_conn.next_arb().await {
         tokio::spawn(async move {
             println!("New arbitrary context accepted");
             let i_bytes = arb.read().await.unwrap();
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use bytes::Bytes;
 use dashmap::DashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -6,7 +7,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{Mutex, mpsc};
 
 use protofish::utp::error::UTPError;
-use protofish::utp::{UTP, UTPEvent};
+use protofish::utp::{TransportIdentity, UTP, UTPEvent};
 use protofish::{IntegrityType, StreamId};
 
 use crate::datagram::DatagramRouter;
@@ -95,6 +96,29 @@ impl UTP for QuicUTP {
         Ok(())
     }
 
+    fn identity(&self) -> TransportIdentity {
+        let peer_certificates = self
+            .connection
+            .peer_identity()
+            .and_then(|identity| {
+                identity
+                    .downcast::<Vec<rustls::pki_types::CertificateDer<'static>>>()
+                    .ok()
+            })
+            .map(|certs| {
+                certs
+                    .iter()
+                    .map(|cert| Bytes::copy_from_slice(cert))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        TransportIdentity {
+            remote_address: Some(self.connection.remote_address()),
+            peer_certificates,
+        }
+    }
+
     async fn next_event(&self) -> UTPEvent {
         let mut rx = self.event_rx.lock().await;
         rx.recv().await.unwrap_or(UTPEvent::UnexpectedClose)
@@ -140,4 +164,12 @@ impl UTP for QuicUTP {
             IntegrityType::Unreliable => Ok(self.add_unreliable_stream(id)),
         }
     }
+
+    fn release_stream(&self, id: StreamId) {
+        self.datagram_router.release(id);
+    }
+
+    async fn close(&self) {
+        self.connection.close(quinn::VarInt::from_u32(0), b"closed");
+    }
 }
@@ -1,15 +1,60 @@
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll, ready};
 
+use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use dashmap::DashMap;
 use protofish::StreamId;
-use tokio::io::{AsyncWriteExt, ReadHalf, SimplexStream, WriteHalf};
+use protofish::utp::UnreliableChannel;
+use protofish::utp::error::UTPError;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{Mutex, mpsc};
+
+/// Datagrams queued per stream before further ones are dropped
+const DATAGRAM_QUEUE_CAPACITY: usize = 1024;
+
+/// Released streams remembered to drop their late datagrams
+const RELEASED_STREAMS_CAPACITY: usize = 4096;
+
+/// Streams buffering datagrams before they are opened, beyond which datagrams
+/// of further unknown streams are dropped
+const PENDING_STREAMS_CAPACITY: usize = 64;
+
+/// The most recently released streams, forgetting the oldest ones first.
+#[derive(Default)]
+struct ReleasedStreams {
+    order: VecDeque<StreamId>,
+    ids: HashSet<StreamId>,
+}
+
+impl ReleasedStreams {
+    fn insert(&mut self, stream_id: StreamId) {
+        if !self.ids.insert(stream_id) {
+            return;
+        }
+
+        self.order.push_back(stream_id);
+        if self.order.len() > RELEASED_STREAMS_CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+    }
+
+    fn contains(&self, stream_id: &StreamId) -> bool {
+        self.ids.contains(stream_id)
+    }
+}
 
 #[derive(Clone)]
 pub struct DatagramRouter {
     conn: Arc<quinn::Connection>,
-    channels: Arc<DashMap<StreamId, WriteHalf<SimplexStream>>>,
-    pending_readers: Arc<DashMap<StreamId, ReadHalf<SimplexStream>>>,
+    channels: Arc<DashMap<StreamId, mpsc::Sender<Bytes>>>,
+    pending_readers: Arc<DashMap<StreamId, mpsc::Receiver<Bytes>>>,
+    released: Arc<std::sync::Mutex<ReleasedStreams>>,
     datagram_chunk_size: usize,
 }
 
@@ -19,30 +64,59 @@ impl DatagramRouter {
             conn,
             channels: Default::default(),
             pending_readers: Default::default(),
+            released: Default::default(),
             datagram_chunk_size,
         }
     }
 
-    pub fn register(&self, stream_id: StreamId) -> ReadHalf<SimplexStream> {
-        if let Some((_, read_half)) = self.pending_readers.remove(&stream_id) {
-            read_half
+    pub fn register(&self, stream_id: StreamId) -> mpsc::Receiver<Bytes> {
+        if let Some((_, receiver)) = self.pending_readers.remove(&stream_id) {
+            receiver
         } else {
-            let (read_half, write_half) = tokio::io::simplex(1024);
+            let (sender, receiver) = mpsc::channel(DATAGRAM_QUEUE_CAPACITY);
 
-            self.channels.insert(stream_id, write_half);
+            // a released stream gets a reader at its end right away
+            if !self.is_released(&stream_id) {
+                self.channels.insert(stream_id, sender);
+            }
 
-            read_half
+            receiver
         }
     }
 
+    /// Stops routing datagrams of a stream the peer closed.
+    ///
+    /// The stream's reader reaches its end once buffered data is consumed;
+    /// datagrams arriving afterwards are dropped. Only the most recently
+    /// released streams are remembered.
+    pub fn release(&self, stream_id: StreamId) {
+        self.released.lock().unwrap().insert(stream_id);
+        self.channels.remove(&stream_id);
+    }
+
+    fn is_released(&self, stream_id: &StreamId) -> bool {
+        self.released.lock().unwrap().contains(stream_id)
+    }
+
     fn register_lazy_writer(&self, stream_id: StreamId) {
-        if !self.channels.contains_key(&stream_id) {
-            let (read_half, write_half) = tokio::io::simplex(1024);
-            self.channels.insert(stream_id, write_half);
-            self.pending_readers.insert(stream_id, read_half);
+        if !self.channels.contains_key(&stream_id)
+            && !self.is_released(&stream_id)
+            && self.pending_readers.len() < PENDING_STREAMS_CAPACITY
+        {
+            let (sender, receiver) = mpsc::channel(DATAGRAM_QUEUE_CAPACITY);
+            self.channels.insert(stream_id, sender);
+            self.pending_readers.insert(stream_id, receiver);
         }
     }
 
+    /// Returns the size of the largest message that fits into one datagram,
+    /// or `None` if the connection does not support datagrams.
+    pub fn max_message_size(&self) -> Option<usize> {
+        self.conn
+            .max_datagram_size()
+            .map(|size| size.saturating_sub(std::mem::size_of::<StreamId>()))
+    }
+
     pub fn write(&self, stream_id: StreamId, data: Bytes) -> crate::error::Result<()> {
         let actual_chunk_size = self.datagram_chunk_size - std::mem::size_of::<StreamId>();
 
@@ -56,6 +130,21 @@ impl DatagramRouter {
             .collect()
     }
 
+    /// Sends a message as a single datagram.
+    pub fn send(&self, stream_id: StreamId, message: Bytes) -> crate::error::Result<()> {
+        let max = self
+            .max_message_size()
+            .ok_or(crate::error::Error::DatagramsUnsupported)?;
+        if message.len() > max {
+            return Err(crate::error::Error::MessageTooLarge {
+                size: message.len(),
+                max,
+            });
+        }
+
+        self.write_chunk(stream_id, &message)
+    }
+
     fn write_chunk(&self, stream_id: StreamId, data: &Bytes) -> crate::error::Result<()> {
         let id_bytes = stream_id.to_le_bytes();
 
@@ -67,15 +156,21 @@ impl DatagramRouter {
         Ok(())
     }
 
-    async fn route_datagram(&self, stream_id: StreamId, data: &Bytes) -> crate::error::Result<()> {
+    fn route_datagram(&self, stream_id: StreamId, data: Bytes) {
+        if self.is_released(&stream_id) {
+            return;
+        }
+
         self.register_lazy_writer(stream_id);
-        let Some(mut channel) = self.channels.get_mut(&stream_id) else {
-            return Err(crate::error::Error::StreamClosed);
+        let Some(channel) = self.channels.get(&stream_id) else {
+            tracing::trace!("Dropped a datagram of unknown stream {}", stream_id);
+            return;
         };
 
-        channel.write_all(data).await?;
-
-        Ok(())
+        // a reader falling behind loses datagrams, like the network would
+        if let Err(TrySendError::Full(_)) = channel.try_send(data) {
+            tracing::trace!("Dropped a datagram of stream {}", stream_id);
+        }
     }
 
     async fn run_listener(&self) -> crate::error::Result<()> {
@@ -90,8 +185,7 @@ impl DatagramRouter {
                     id_bytes.copy_from_slice(&data[..8]);
                     let stream_id = u64::from_le_bytes(id_bytes);
 
-                    let payload = data.slice(8..);
-                    self.route_datagram(stream_id, &payload).await?;
+                    self.route_datagram(stream_id, data.slice(8..));
                 }
                 Err(err) => {
                     break Err(crate::error::Error::from(err));
@@ -118,3 +212,89 @@ impl DatagramRouter {
         });
     }
 }
+
+/// Reads the datagrams of a stream as a byte stream.
+pub struct DatagramReader {
+    receiver: mpsc::Receiver<Bytes>,
+    buffered: Bytes,
+}
+
+impl DatagramReader {
+    pub fn new(receiver: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            receiver,
+            buffered: Bytes::new(),
+        }
+    }
+
+    pub fn into_receiver(self) -> mpsc::Receiver<Bytes> {
+        self.receiver
+    }
+}
+
+impl AsyncRead for DatagramReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        while this.buffered.is_empty() {
+            match ready!(this.receiver.poll_recv(cx)) {
+                Some(datagram) => this.buffered = datagram,
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let len = this.buffered.len().min(buf.remaining());
+        buf.put_slice(&this.buffered.split_to(len));
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Sends and receives the messages of a stream as whole datagrams.
+pub struct DatagramChannel {
+    router: DatagramRouter,
+    stream_id: StreamId,
+    receiver: Mutex<mpsc::Receiver<Bytes>>,
+}
+
+impl DatagramChannel {
+    pub fn new(
+        router: DatagramRouter,
+        stream_id: StreamId,
+        receiver: mpsc::Receiver<Bytes>,
+    ) -> Self {
+        Self {
+            router,
+            stream_id,
+            receiver: receiver.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl UnreliableChannel for DatagramChannel {
+    fn max_message_size(&self) -> usize {
+        // datagram support is agreed once per connection and checked by
+        // into_channel
+        self.router.max_message_size().unwrap_or_default()
+    }
+
+    async fn send(&self, message: Bytes) -> Result<(), UTPError> {
+        self.router.send(self.stream_id, message)?;
+
+        Ok(())
+    }
+
+    async fn recv(&self) -> Result<Bytes, UTPError> {
+        self.receiver
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(UTPError::ChannelClosed)
+    }
+}
@@ -20,6 +20,12 @@ pub enum Error {
     #[error("Datagram error: {0}")]
     Datagram(String),
 
+    #[error("Message of {size} bytes exceeds the datagram MTU of {max} bytes")]
+    MessageTooLarge { size: usize, max: usize },
+
+    #[error("Datagrams are not supported on this connection")]
+    DatagramsUnsupported,
+
     #[error("Configuration error: {0}")]
     Config(String),
 
@@ -38,7 +44,13 @@ pub enum Error {
 
 impl From<Error> for protofish::utp::error::UTPError {
     fn from(err: Error) -> Self {
-        protofish::utp::error::UTPError::Fatal(err.to_string())
+        match err {
+            Error::MessageTooLarge { size, max } => {
+                protofish::utp::error::UTPError::MessageTooLarge { size, max }
+            }
+            Error::DatagramsUnsupported => protofish::utp::error::UTPError::ChannelUnsupported,
+            err => protofish::utp::error::UTPError::Fatal(err.to_string()),
+        }
     }
 }
 
@@ -1,12 +1,13 @@
 use bytes::Bytes;
 use std::io::ErrorKind;
 use std::task::Poll;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, SimplexStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use protofish::utp::UTPStream;
+use protofish::utp::error::UTPError;
 use protofish::{IntegrityType, StreamId};
 
-use crate::datagram::DatagramRouter;
+use crate::datagram::{DatagramChannel, DatagramReader, DatagramRouter};
 
 pub struct QuicUTPStream {
     id: StreamId,
@@ -22,7 +23,7 @@ pub enum StreamWriteInner {
 
 pub enum StreamReadInner {
     Reliable(quinn::RecvStream),
-    Unreliable(ReadHalf<SimplexStream>),
+    Unreliable(DatagramReader),
 }
 
 impl QuicUTPStream {
@@ -36,13 +37,13 @@ impl QuicUTPStream {
     }
 
     pub fn new_unreliable(id: StreamId, router: DatagramRouter) -> Self {
-        let read_half = router.register(id);
+        let reader = DatagramReader::new(router.register(id));
 
         Self {
             id,
             integrity_type: IntegrityType::Unreliable,
             writer: StreamWriteInner::Unreliable(router, id),
-            reader: StreamReadInner::Unreliable(read_half),
+            reader: StreamReadInner::Unreliable(reader),
         }
     }
 }
@@ -50,6 +51,7 @@ impl QuicUTPStream {
 impl UTPStream for QuicUTPStream {
     type StreamRead = StreamReadInner;
     type StreamWrite = StreamWriteInner;
+    type Channel = DatagramChannel;
 
     fn id(&self) -> StreamId {
         self.id
@@ -62,6 +64,17 @@ impl UTPStream for QuicUTPStream {
     fn split(self) -> (Self::StreamWrite, Self::StreamRead) {
         (self.writer, self.reader)
     }
+
+    fn into_channel(self) -> Result<Self::Channel, UTPError> {
+        match (self.writer, self.reader) {
+            (StreamWriteInner::Unreliable(router, id), StreamReadInner::Unreliable(reader))
+                if router.max_message_size().is_some() =>
+            {
+                Ok(DatagramChannel::new(router, id, reader.into_receiver()))
+            }
+            _ => Err(UTPError::ChannelUnsupported),
+        }
+    }
 }
 
 impl AsyncRead for StreamReadInner {
@@ -272,3 +272,173 @@ async fn test_multiple_streams() {
 
     assert!(server_result);
 }
+
+#[tokio::test]
+async fn test_unreliable_stream_close() {
+    use protofish::utp::{UTP, UTPStream};
+    use tokio::sync::oneshot;
+
+    let (server_crypto, client_crypto) = create_test_certs();
+
+    let server_config = QuicConfig::server_default().with_server_crypto(server_crypto);
+    let server_endpoint = QuicEndpoint::server("127.0.0.1:0".parse().unwrap(), server_config)
+        .expect("Failed to create server endpoint");
+    let server_addr = server_endpoint.local_addr().unwrap();
+
+    let (released_tx, released_rx) = oneshot::channel();
+    let (late_tx, late_rx) = oneshot::channel();
+
+    let server_handle = tokio::spawn(async move {
+        let conn = server_endpoint.accept().await.unwrap();
+        let utp = Arc::new(QuicUTP::new(conn, true));
+        let conn = protofish::accept(utp.clone())
+            .await
+            .expect("failed to accept");
+
+        let arb = conn.next_arb().await.unwrap();
+        let stream = arb.wait_stream().await.unwrap();
+        let stream_id = stream.id();
+        let (_writer, mut reader) = stream.split();
+
+        // the reader only ends once StreamClose released the stream
+        let mut received = Vec::new();
+        timeout(Duration::from_secs(2), reader.read_to_end(&mut received))
+            .await
+            .expect("Reader should reach EOF after StreamClose")
+            .unwrap();
+
+        // a released stream is not routed to anymore, so its late datagrams
+        // never reach a new reader
+        let late = utp
+            .wait_stream(stream_id, IntegrityType::Unreliable)
+            .await
+            .unwrap();
+        let (_writer, mut late_reader) = late.split();
+        released_tx.send(()).unwrap();
+        late_rx.await.unwrap();
+
+        let mut late_received = Vec::new();
+        timeout(
+            Duration::from_secs(1),
+            late_reader.read_to_end(&mut late_received),
+        )
+        .await
+        .expect("Reader of a released stream should end right away")
+        .unwrap();
+
+        (received, late_received)
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client_config = QuicConfig::client_default().with_client_crypto(client_crypto);
+    let client_endpoint = QuicEndpoint::client("127.0.0.1:0".parse().unwrap(), client_config)
+        .expect("Failed to create client endpoint");
+
+    let conn = client_endpoint
+        .connect(server_addr, "localhost")
+        .await
+        .unwrap();
+    let client_utp = Arc::new(QuicUTP::new(conn, false));
+    let client_conn = protofish::connect(client_utp, "example.com").await.unwrap();
+    let arb = client_conn.new_arb();
+
+    let stream = arb.new_stream(IntegrityType::Unreliable).await.unwrap();
+    let (mut writer, _reader) = stream.split();
+
+    writer.write_all(&[1u8; 100]).await.unwrap();
+    writer.shutdown().await.unwrap();
+
+    // datagrams still go out after the stream was closed
+    released_rx.await.unwrap();
+    writer.write_all(&[2u8; 100]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    late_tx.send(()).unwrap();
+
+    let (received, late_received) = timeout(Duration::from_secs(3), server_handle)
+        .await
+        .expect("Server timeout")
+        .expect("Server task failed");
+
+    assert!(received.len() <= 100);
+    assert!(received.iter().all(|&byte| byte == 1));
+    assert!(late_received.is_empty());
+}
+
+#[tokio::test]
+async fn test_unreliable_channel() {
+    use protofish::utp::UnreliableChannel;
+    use protofish::utp::error::UTPError;
+
+    let (server_crypto, client_crypto) = create_test_certs();
+
+    let server_config = QuicConfig::server_default().with_server_crypto(server_crypto);
+    let server_endpoint = QuicEndpoint::server("127.0.0.1:0".parse().unwrap(), server_config)
+        .expect("Failed to create server endpoint");
+    let server_addr = server_endpoint.local_addr().unwrap();
+
+    let server_handle = tokio::spawn(async move {
+        let conn = server_endpoint.accept().await.unwrap();
+        let utp = Arc::new(QuicUTP::new(conn, true));
+        let conn = protofish::accept(utp).await.expect("failed to accept");
+
+        let arb = conn.next_arb().await.unwrap();
+        let channel = arb.wait_channel().await.unwrap();
+
+        let mut received = Vec::new();
+        loop {
+            match timeout(Duration::from_secs(2), channel.recv()).await {
+                Ok(Ok(message)) => received.push(message),
+                Ok(Err(UTPError::ChannelClosed)) => break,
+                other => panic!("Channel should end after StreamClose: {:?}", other),
+            }
+        }
+
+        received
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client_config = QuicConfig::client_default().with_client_crypto(client_crypto);
+    let client_endpoint = QuicEndpoint::client("127.0.0.1:0".parse().unwrap(), client_config)
+        .expect("Failed to create client endpoint");
+
+    let conn = client_endpoint
+        .connect(server_addr, "localhost")
+        .await
+        .unwrap();
+    let client_utp = Arc::new(QuicUTP::new(conn, false));
+    let client_conn = protofish::connect(client_utp, "example.com").await.unwrap();
+    let arb = client_conn.new_arb();
+
+    let channel = arb.new_channel().await.unwrap();
+    let max = channel.max_message_size();
+    assert!(max > 0);
+
+    let messages: Vec<Bytes> = [1, 100, max]
+        .into_iter()
+        .enumerate()
+        .map(|(i, len)| Bytes::from(vec![i as u8; len]))
+        .collect();
+    for message in &messages {
+        channel.send(message.clone()).await.unwrap();
+    }
+
+    let oversized = Bytes::from(vec![0u8; max + 1]);
+    assert!(matches!(
+        channel.send(oversized).await,
+        Err(UTPError::MessageTooLarge { size, max: limit }) if size == max + 1 && limit == max
+    ));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop(channel);
+
+    let received = timeout(Duration::from_secs(3), server_handle)
+        .await
+        .expect("Server timeout")
+        .expect("Server task failed");
+
+    // datagrams may be lost, but never split or merged
+    assert!(received.len() <= messages.len());
+    assert!(received.iter().all(|message| messages.contains(message)));
+}